@@ -1,4 +1,8 @@
-use std::fs;
+use std::{
+    fs, io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::Path,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -21,6 +25,37 @@ pub fn get_config() -> Configuration {
     configuration
 }
 
+/// Load a configuration file if it exists
+///
+/// Returns `Ok(None)` when the file is missing and an `InvalidData` error
+/// when the file can't be parsed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let config = pillow_config::read_config("pillow.toml").unwrap();
+/// ```
+pub fn read_config<P: AsRef<Path>>(filename: P) -> io::Result<Option<Configuration>> {
+    let filename = filename.as_ref();
+
+    let contents = match fs::read_to_string(filename) {
+        Ok(c) => c,
+
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+
+        Err(err) => return Err(err),
+    };
+
+    match toml::from_str(&contents) {
+        Ok(c) => Ok(Some(c)),
+
+        Err(err) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unable to load data from {} {}", filename.display(), err),
+        )),
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Configuration {
     app: Option<App>,
@@ -34,27 +69,18 @@ pub struct Configuration {
 
 impl Configuration {
     pub fn app(self) -> App {
-        match self.app {
-            Some(app) => app,
+        self.app.unwrap_or_default()
+    }
 
-            None => App {
-                name: String::from("Pillow"),
-                debug: true,
-            },
-        }
+    /// `debug` of `[app]`, `true` without `[app]`
+    pub fn debug(&self) -> bool {
+        self.app
+            .as_ref()
+            .map_or(App::default().debug, |app| app.debug)
     }
 
     pub fn server(self) -> Server {
-        match self.server {
-            Some(s) => s,
-
-            None => Server {
-                port: 3000,
-                url: String::from("http://localhost"),
-                address: [127, 0, 0, 1],
-                ssl: None,
-            },
-        }
+        self.server.unwrap_or_default()
     }
 
     pub fn router() {}
@@ -70,6 +96,15 @@ pub struct App {
     pub debug: bool,
 }
 
+impl Default for App {
+    fn default() -> Self {
+        App {
+            name: String::from("Pillow"),
+            debug: true,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Router {
     directory: Directory,
@@ -85,10 +120,154 @@ pub struct Directory {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Server {
+    #[serde(default = "default_port")]
     pub port: u16,
+    #[serde(default = "default_url")]
     pub url: String,
-    pub address: [u8; 4],
+    /// Like `[127, 0, 0, 1]`, `"::1"` or `"localhost"`
+    #[serde(default)]
+    pub address: Address,
     ssl: Option<Ssl>,
+
+    /// Ports to try after `port` when it's in use
+    pub try_next_port: Option<u16>,
+
+    /// Listen on these addresses instead of `address` and `port`
+    pub listeners: Option<Vec<ListenerConfig>>,
+
+    /// Path of a Unix domain socket to listen instead of `address` and `port`
+    pub unix_socket: Option<String>,
+
+    /// Permissions of the Unix domain socket, like `0o660`
+    pub unix_socket_mode: Option<u32>,
+
+    /// Remove a stale Unix domain socket, `true` by default
+    pub unix_socket_remove_stale: Option<bool>,
+
+    /// Connection limits
+    pub limits: Option<Limits>,
+
+    /// Connection timeouts
+    pub timeouts: Option<Timeouts>,
+}
+
+fn default_port() -> u16 {
+    3000
+}
+
+fn default_url() -> String {
+    String::from("http://localhost")
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server {
+            port: default_port(),
+            url: default_url(),
+            address: Address::default(),
+            ssl: None,
+            try_next_port: None,
+            listeners: None,
+            unix_socket: None,
+            unix_socket_mode: None,
+            unix_socket_remove_stale: None,
+            limits: None,
+            timeouts: None,
+        }
+    }
+}
+
+/// Address to listen: IPv4, IPv6 or a hostname
+///
+/// ```toml
+/// address = [127, 0, 0, 1]
+/// address = "::1"
+/// address = "localhost"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Address {
+    Octets([u8; 4]),
+    Host(String),
+}
+
+impl Default for Address {
+    fn default() -> Self {
+        Address::Octets([127, 0, 0, 1])
+    }
+}
+
+impl Address {
+    /// Resolve the address with a port
+    ///
+    /// With a hostname the first resolved address is used
+    pub fn to_socket_addr(&self, port: u16) -> io::Result<SocketAddr> {
+        match self {
+            Address::Octets(octets) => Ok(SocketAddr::from((*octets, port))),
+
+            Address::Host(host) => {
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+
+                if let Ok(ip) = host.parse::<IpAddr>() {
+                    return Ok(SocketAddr::from((ip, port)));
+                }
+
+                (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Could not resolve address {}", host),
+                    )
+                })
+            }
+        }
+    }
+}
+
+impl From<[u8; 4]> for Address {
+    fn from(octets: [u8; 4]) -> Self {
+        Address::Octets(octets)
+    }
+}
+
+impl From<IpAddr> for Address {
+    fn from(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(ip) => Address::Octets(ip.octets()),
+            IpAddr::V6(ip) => Address::Host(ip.to_string()),
+        }
+    }
+}
+
+impl From<&str> for Address {
+    fn from(host: &str) -> Self {
+        Address::Host(host.to_string())
+    }
+}
+
+impl From<String> for Address {
+    fn from(host: String) -> Self {
+        Address::Host(host)
+    }
+}
+
+/// `[[server.listeners]]`
+///
+/// ```toml
+/// [[server.listeners]]
+/// address = "::"
+/// port = 80
+///
+/// [[server.listeners]]
+/// address = "::"
+/// port = 443
+/// ssl = { cert = "cert.pem", key = "key.pem" }
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ListenerConfig {
+    #[serde(default)]
+    pub address: Address,
+    pub port: u16,
+    pub ssl: Option<Ssl>,
 }
 
 impl Server {
@@ -97,12 +276,167 @@ impl Server {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Ssl {
+    /// PEM certificate, with the rustls feature it can be a chain
+    pub cert: String,
+
+    /// PEM private key
+    pub key: String,
+
+    /// Minimum TLS version: "1.2" or "1.3"
+    pub min_version: Option<String>,
+
+    /// Cipher suites like "TLS13_AES_256_GCM_SHA384", rustls only
+    pub ciphers: Option<Vec<String>>,
+
+    /// ALPN protocols to advertise, `["http/1.1"]` by default, rustls only
+    pub alpn: Option<Vec<String>>,
+    /// Certificates chosen by the SNI server name,
+    /// `cert` and `key` are used for the other names
+    pub certificates: Option<Vec<SslCertificate>>,
+    /// PEM bundle of the CAs of the client certificates, rustls only
+    pub client_ca: Option<String>,
+
+    /// Client certificates policy, `required` when `client_ca` is set
+    pub client_auth: Option<ClientAuth>,
+    /// Check the certificate files every N milliseconds and reload them when they change
+    pub watch: Option<u64>,
+
+    /// Reload the certificate files when the process gets SIGHUP
+    pub reload_on_sighup: Option<bool>,
+
+    /// Plain http listener redirecting to https
+    pub redirect: Option<SslRedirect>,
+
+    /// Strict-Transport-Security header of the https responses
+    pub hsts: Option<Hsts>,
+
+    /// Use a self-signed certificate for localhost instead of `cert` and `key`,
+    /// only with `[app] debug = true`
+    pub dev_cert: Option<bool>,
+
+    /// Directory of the self-signed certificate, `target/pillow/dev-cert` by default
+    pub dev_cert_dir: Option<String>,
+}
+
+impl Ssl {
+    /// Client certificates policy of the configuration
+    pub fn client_auth(&self) -> ClientAuth {
+        match (self.client_auth, &self.client_ca) {
+            (Some(client_auth), _) => client_auth,
+            (None, Some(_)) => ClientAuth::Required,
+            (None, None) => ClientAuth::None,
+        }
+    }
+}
+
+/// Client certificates policy of mutual TLS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    /// Reject clients without a valid certificate
+    Required,
+
+    /// Verify the certificate when the client sends one
+    Optional,
+
+    /// Don't ask for client certificates
+    #[default]
+    None,
+}
+
+/// `[[server.ssl.certificates]]`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SslCertificate {
+    /// Server names of the certificate, like "example.com" or "*.example.com"
+    pub hostnames: Vec<String>,
+
+    /// PEM certificate
     pub cert: String,
+
+    /// PEM private key
     pub key: String,
 }
 
+/// `[server.ssl.redirect]`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SslRedirect {
+    /// Port of the http listener, 80 by default
+    pub port: Option<u16>,
+
+    /// 301 or 308, 301 by default
+    pub status: Option<u16>,
+}
+
+/// `[server.ssl.hsts]`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Hsts {
+    /// Seconds the browsers only use https, one year by default
+    pub max_age: Option<u64>,
+
+    /// Also for the subdomains
+    pub include_subdomains: Option<bool>,
+
+    /// Allow the inclusion in the browsers preload lists
+    pub preload: Option<bool>,
+}
+
+impl Hsts {
+    /// Value of the Strict-Transport-Security header
+    pub fn header_value(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age.unwrap_or(31_536_000));
+
+        if self.include_subdomains.unwrap_or(false) {
+            value.push_str("; includeSubDomains");
+        }
+
+        if self.preload.unwrap_or(false) {
+            value.push_str("; preload");
+        }
+
+        value
+    }
+}
+
+/// `[server.limits]`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Limits {
+    /// Pending connections queue of the listener
+    pub backlog: Option<u32>,
+
+    /// Max size in bytes of a request
+    pub max_request_size: Option<usize>,
+
+    /// Max open connections of the server
+    pub max_connections: Option<usize>,
+
+    /// Max open connections of a client IP
+    pub max_connections_per_ip: Option<usize>,
+}
+
+/// `[server.timeouts]`, values in milliseconds
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Timeouts {
+    /// Max time to read the headers and the body of a request
+    pub read: Option<u64>,
+
+    /// Max time to read the headers of a request
+    pub header_read: Option<u64>,
+
+    /// Max time to read the body of a request
+    pub body_read: Option<u64>,
+
+    /// Max time to write a response
+    pub write: Option<u64>,
+
+    /// Max idle time between two requests of a connection
+    pub keep_alive: Option<u64>,
+
+    /// Max time of the TLS handshake
+    pub tls_handshake: Option<u64>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Database {
     pub connection: String,
@@ -0,0 +1,160 @@
+//! Directories baked into the binary with `embed_dir!`
+
+/// Directory baked into the binary, its files are sorted by path
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_fs::embed::EmbeddedDir;
+///
+/// static SOURCES: EmbeddedDir = pillow_fs::embed_dir!("src");
+///
+/// let lib = SOURCES.get("lib.rs").unwrap();
+///
+/// assert_eq!(lib.path(), "lib.rs");
+/// assert!(SOURCES.get("../Cargo.toml").is_none());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedDir {
+    path: &'static str,
+    files: &'static [EmbeddedFile],
+}
+
+/// File of an EmbeddedDir, with the MIME type and the ETag of its content
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedFile {
+    path: &'static str,
+    content: &'static [u8],
+    content_type: &'static str,
+    etag: &'static str,
+}
+
+impl EmbeddedDir {
+    /// Used by `embed_dir!`, the files have to be sorted by path
+    #[doc(hidden)]
+    pub const fn new(path: &'static str, files: &'static [EmbeddedFile]) -> Self {
+        Self { path, files }
+    }
+
+    /// Directory embedded, relative to the crate
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    pub fn files(&self) -> &'static [EmbeddedFile] {
+        self.files
+    }
+
+    /// File of a path relative to the directory, like `css/app.css`
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        self.files
+            .binary_search_by(|file| file.path.cmp(path))
+            .ok()
+            .map(|index| &self.files[index])
+    }
+
+    /// There are files under a path, `""` is the directory itself
+    pub fn is_dir(&self, path: &str) -> bool {
+        path.is_empty()
+            || self
+                .files
+                .iter()
+                .any(|file| under(file.path, path).is_some())
+    }
+
+    /// Names of the entries of a subdirectory, with `true` for the directories
+    pub fn entries(&self, directory: &str) -> Vec<(&'static str, bool)> {
+        let mut entries: Vec<(&'static str, bool)> = Vec::new();
+
+        for file in self.files {
+            let Some(rest) = under(file.path, directory) else {
+                continue;
+            };
+
+            let entry = match rest.split_once('/') {
+                Some((name, _)) => (name, true),
+                None => (rest, false),
+            };
+
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+
+        entries
+    }
+}
+
+impl EmbeddedFile {
+    /// Used by `embed_dir!`
+    #[doc(hidden)]
+    pub const fn new(
+        path: &'static str,
+        content: &'static [u8],
+        content_type: &'static str,
+        etag: &'static str,
+    ) -> Self {
+        Self {
+            path,
+            content,
+            content_type,
+            etag,
+        }
+    }
+
+    /// Path relative to the directory, with `/` between the segments
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    pub fn content(&self) -> &'static [u8] {
+        self.content
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    /// Strong ETag, quoted
+    pub fn etag(&self) -> &'static str {
+        self.etag
+    }
+}
+
+/// Rest of a path under a directory
+fn under<'a>(path: &'a str, directory: &str) -> Option<&'a str> {
+    match directory {
+        "" => Some(path),
+        directory => path.strip_prefix(directory)?.strip_prefix('/'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DIR: EmbeddedDir = EmbeddedDir::new(
+        "public",
+        &[
+            EmbeddedFile::new("css/app.css", b"body {}", "text/css", "\"a\""),
+            EmbeddedFile::new("css/vendor/reset.css", b"", "text/css", "\"b\""),
+            EmbeddedFile::new("index.html", b"<h1>pillow</h1>", "text/html", "\"c\""),
+        ],
+    );
+
+    #[test]
+    fn it_finds_the_files_and_the_directories() {
+        assert_eq!(DIR.get("index.html").unwrap().content(), b"<h1>pillow</h1>");
+        assert!(DIR.get("css").is_none());
+
+        assert!(DIR.is_dir("css"));
+        assert!(DIR.is_dir("css/vendor"));
+        assert!(!DIR.is_dir("cs"));
+
+        assert_eq!(DIR.entries(""), vec![("css", true), ("index.html", false)]);
+        assert_eq!(
+            DIR.entries("css"),
+            vec![("app.css", false), ("vendor", true)]
+        );
+    }
+}
@@ -1,6 +1,44 @@
 use mime_guess::MimeGuess;
 use std::{fs, io::Read, path::Path};
 
+pub mod embed;
+
+pub use embed::{EmbeddedDir, EmbeddedFile};
+
+#[doc(hidden)]
+pub use pillow_macros::embed_dir as __embed_dir;
+
+/// Bake a directory into the binary, the path is relative to the crate
+///
+/// The MIME types and the ETags are computed at compile time, and a change
+/// of a file rebuilds the crate.
+///
+/// Cargo doesn't track the directory itself, so adding or removing a file
+/// doesn't rebuild the crate. A build script watching the directory does it:
+///
+/// ```rust,ignore
+/// // build.rs
+/// fn main() {
+///     println!("cargo:rerun-if-changed=public");
+/// }
+/// ```
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_fs::EmbeddedDir;
+///
+/// static SOURCES: EmbeddedDir = pillow_fs::embed_dir!("src");
+///
+/// assert_eq!(SOURCES.get("lib.rs").unwrap().content_type(), "text/x-rust");
+/// ```
+#[macro_export]
+macro_rules! embed_dir {
+    ($path:literal) => {
+        $crate::__embed_dir!($crate, $path)
+    };
+}
+
 pub struct FS {}
 
 #[derive(Debug, Clone)]
@@ -123,7 +161,8 @@ impl FS {
         }
     }
 
-    fn get_mime_type(path: &str) -> String {
+    /// Mime type guessed from the extension, `application/octet-stream` when unknown
+    pub fn get_mime_type(path: &str) -> String {
         let path = Path::new(path);
 
         MimeGuess::from_path(path)
@@ -0,0 +1,103 @@
+//! Dates of the HTTP headers
+
+use std::{
+    cell::RefCell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// IMF-fixdate of RFC 9110, like `Sun, 06 Nov 1994 08:49:37 GMT`
+const IMF_FIXDATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Obsolete formats still accepted from the clients
+const RFC_850: &str = "%A, %d-%b-%y %H:%M:%S GMT";
+const ASCTIME: &str = "%a %b %e %H:%M:%S %Y";
+
+thread_local! {
+    /// Second and date of the last `now`
+    static NOW: RefCell<(u64, String)> = const { RefCell::new((u64::MAX, String::new())) };
+}
+
+/// Date of a header like Last-Modified
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let date = pillow_http::date::http_date(UNIX_EPOCH + Duration::from_secs(784111777));
+///
+/// assert_eq!(date, "Sun, 06 Nov 1994 08:49:37 GMT");
+/// ```
+pub fn http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format(IMF_FIXDATE).to_string()
+}
+
+/// Current date for the Date header, formatted once per second
+pub fn now() -> String {
+    let time = SystemTime::now();
+    let second = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+
+    NOW.with(|now| {
+        let mut now = now.borrow_mut();
+
+        if now.0 != second {
+            *now = (second, http_date(time));
+        }
+
+        now.1.clone()
+    })
+}
+
+/// Date of a header like If-Modified-Since, `None` if it's not an HTTP date
+///
+/// The IMF-fixdate, and the obsolete RFC 850 and asctime formats are accepted
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_http::date::parse_http_date;
+///
+/// assert_eq!(
+///     parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+///     parse_http_date("Sun Nov  6 08:49:37 1994")
+/// );
+/// ```
+pub fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let date = date.trim();
+
+    [IMF_FIXDATE, RFC_850, ASCTIME]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .map(|date| DateTime::<Utc>::from_utc(date, Utc).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn it_parses_the_http_dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+
+        assert_eq!(parse_http_date(&http_date(time)), Some(time));
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(time)
+        );
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), Some(time));
+        assert_eq!(parse_http_date("1994-11-06 08:49:37 +00:00"), None);
+    }
+
+    #[test]
+    fn it_caches_the_date_of_the_second() {
+        let date = now();
+
+        assert!(date.ends_with(" GMT"));
+        assert!(parse_http_date(&date).is_some());
+    }
+}
@@ -6,6 +6,8 @@ pub enum Header {
     /// Cors
     AccessControlAllowOrigin,
     Accept,
+    /// Charsets
+    AcceptCharset,
     /// How to encoding to send files
     AcceptEncoding,
     /// Languages
@@ -19,8 +21,12 @@ pub enum Header {
     /// Keep Connection
     Connection,
     ContentDisposition,
+    /// Compression of the body
+    ContentEncoding,
     /// Content Length
     ContentLength,
+    /// Part of the content in a 206 response
+    ContentRange,
     /// Content Segurity Policy
     ContentSegurityPolicy,
     /// Content Type
@@ -31,10 +37,20 @@ pub enum Header {
     ETag,
     /// Where
     Host,
+    /// ETags of the cached versions of the client
+    IfNoneMatch,
+    /// Date of the cached version of the client
+    IfModifiedSince,
+    /// Version of the content that Range applies to
+    IfRange,
+    /// Last event received by a client of Server-Sent Events
+    LastEventId,
     /// Last Modified
     LastModified,
     ///
     Location,
+    /// Parts of the content asked by the client
+    Range,
     ///
     SecFetchDest,
     ///
@@ -51,6 +67,8 @@ pub enum Header {
     Server,
     /// Cookies
     SetCookie,
+    /// HSTS
+    StrictTransportSecurity,
     /// Encoding content
     TransferEncoding,
     /// Upgrade
@@ -68,25 +86,34 @@ impl Header {
         match self {
             Header::AccessControlAllowOrigin => "Access-Control-Allow-Origin",
             Header::Accept => "Accept",
+            Header::AcceptCharset => "Accept-Charset",
             Header::AcceptEncoding => "Accept-Encoding",
-            Header::AcceptLanguage => "Accept-Languages",
+            Header::AcceptLanguage => "Accept-Language",
             Header::AcceptRanges => "Accept-Ranges",
 
             Header::CacheControl => "Cache-Control",
             Header::Cookie => "Cookie",
             Header::Connection => "Connection",
             Header::ContentDisposition => "Content-Disposition",
+            Header::ContentEncoding => "Content-Encoding",
             Header::ContentLength => "Content-Length",
+            Header::ContentRange => "Content-Range",
             Header::ContentSegurityPolicy => "Content-Segurity-Policy",
             Header::ContentType => "Content-Type",
 
             Header::Date => "Date",
             Header::ETag => "ETag",
             Header::Host => "Host",
+            Header::IfNoneMatch => "If-None-Match",
+            Header::IfModifiedSince => "If-Modified-Since",
+            Header::IfRange => "If-Range",
 
             Header::Location => "Location",
+            Header::LastEventId => "Last-Event-ID",
             Header::LastModified => "Last-Modified",
 
+            Header::Range => "Range",
+
             Header::SecFetchDest => "Sec-Fetch-Dest",
             Header::SecWebSocketAccept => "Sec-WebSocket-Accept",
             Header::SecWebSocketKey => "Sec-WebSocket-Key",
@@ -96,6 +123,7 @@ impl Header {
 
             Header::Server => "Server",
             Header::SetCookie => "Set-Cookie",
+            Header::StrictTransportSecurity => "Strict-Transport-Security",
 
             Header::TransferEncoding => "Transfer-Encoding",
             Header::Upgrade => "Upgrade",
@@ -111,6 +139,7 @@ pub fn from_string_to_header(header: String) -> Header {
     match header.to_lowercase().as_str() {
         "access-control-allow-origin" => Header::AccessControlAllowOrigin,
         "accept" => Header::Accept,
+        "accept-charset" => Header::AcceptCharset,
         "accept-encoding" => Header::AcceptEncoding,
         "accept-language" => Header::AcceptLanguage,
         "accept-ranges" => Header::AcceptRanges,
@@ -119,26 +148,35 @@ pub fn from_string_to_header(header: String) -> Header {
         "cookie" => Header::Cookie,
         "connection" => Header::Connection,
         "content-disposition" => Header::ContentDisposition,
+        "content-encoding" => Header::ContentEncoding,
         "content-length" => Header::ContentLength,
+        "content-range" => Header::ContentRange,
         "content-segurity-policy" => Header::ContentSegurityPolicy,
         "content-type" => Header::ContentType,
 
         "date" => Header::Date,
         "etag" => Header::ETag,
         "host" => Header::Host,
+        "if-none-match" => Header::IfNoneMatch,
+        "if-modified-since" => Header::IfModifiedSince,
+        "if-range" => Header::IfRange,
 
         "location" => Header::Location,
+        "last-event-id" => Header::LastEventId,
         "last-modified" => Header::LastModified,
 
+        "range" => Header::Range,
+
         "sec-fetch-dest" => Header::SecFetchDest,
-        "sec-webSocket-accept" => Header::SecWebSocketAccept,
-        "sec-webSocket-key" => Header::SecWebSocketKey,
-        "sec-webSocket-origin" => Header::SecWebSocketOrigin,
-        "sec-webSocket-protocol" => Header::SecWebSocketProtocol,
-        "sec-webSocket-version" => Header::SecWebSocketVersion,
+        "sec-websocket-accept" => Header::SecWebSocketAccept,
+        "sec-websocket-key" => Header::SecWebSocketKey,
+        "sec-websocket-origin" => Header::SecWebSocketOrigin,
+        "sec-websocket-protocol" => Header::SecWebSocketProtocol,
+        "sec-websocket-version" => Header::SecWebSocketVersion,
 
         "server" => Header::Server,
         "set-cookie" => Header::SetCookie,
+        "strict-transport-security" => Header::StrictTransportSecurity,
 
         "transfer-encoding" => Header::TransferEncoding,
         "upgrade" => Header::Upgrade,
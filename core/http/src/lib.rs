@@ -5,6 +5,7 @@
 pub mod body;
 pub mod controller;
 mod cors;
+pub mod date;
 pub mod futures_handler;
 pub mod handler;
 pub mod header;
@@ -14,16 +15,23 @@ mod params;
 mod request;
 mod response;
 mod uri;
+pub mod websocket;
 
+pub use response::compression;
+pub use response::sse;
+pub use response::static_dir;
 pub use response::static_files;
 
-pub use request::Request;
+pub use request::{PeerCertificate, Request};
 pub use response::Response;
 
 pub use response::Body as BodyResponse;
+pub use response::BodyStream;
 
 pub use serde_json::json;
 
+pub use pillow_fs::embed_dir;
+
 pub use cors::Cors;
 pub use uri::Uri;
 
@@ -0,0 +1,187 @@
+//! Content negotiation with the Accept headers of a request
+
+use super::Request;
+use crate::header::Header;
+
+impl Request {
+    /// Media type of `types` preferred by the Accept of the request
+    ///
+    /// The quality values of the client come first, then the order of `types`.
+    /// Without Accept every type is acceptable, `None` when none is
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::Request;
+    ///
+    /// let request = b"GET / HTTP/1.1\r\nAccept: text/html, */*;q=0.8\r\n\r\n".to_vec();
+    /// let request = Request::from_vec(&request).unwrap();
+    ///
+    /// assert_eq!(
+    ///     request.accepts(&["application/json", "text/html"]),
+    ///     Some("text/html")
+    /// );
+    /// ```
+    pub fn accepts<'a>(&self, types: &[&'a str]) -> Option<&'a str> {
+        preferred(
+            self.headers.get(&Header::Accept),
+            types,
+            |range, offered| {
+                let (range_type, range_subtype) = range.split_once('/')?;
+                let (offered_type, offered_subtype) = offered.split_once('/')?;
+
+                match (range_type, range_subtype) {
+                    ("*", "*") => Some(0),
+                    (range_type, "*") if range_type == offered_type => Some(1),
+                    (range_type, range_subtype)
+                        if range_type == offered_type && range_subtype == offered_subtype =>
+                    {
+                        Some(2)
+                    }
+                    _ => None,
+                }
+            },
+        )
+    }
+
+    /// Language tag of `languages` preferred by the Accept-Language of the request
+    ///
+    /// A range like `en` matches `en` and `en-US`, the most specific range gives the quality
+    pub fn accepts_language<'a>(&self, languages: &[&'a str]) -> Option<&'a str> {
+        preferred(
+            self.headers.get(&Header::AcceptLanguage),
+            languages,
+            |range, offered| match range {
+                "*" => Some(0),
+                range
+                    if offered == range
+                        || offered
+                            .strip_prefix(range)
+                            .is_some_and(|rest| rest.starts_with('-')) =>
+                {
+                    Some(range.split('-').count())
+                }
+                _ => None,
+            },
+        )
+    }
+
+    /// Charset of `charsets` preferred by the Accept-Charset of the request
+    pub fn accepts_charset<'a>(&self, charsets: &[&'a str]) -> Option<&'a str> {
+        preferred(
+            self.headers.get(&Header::AcceptCharset),
+            charsets,
+            |range, offered| match range {
+                "*" => Some(0),
+                range if range == offered => Some(1),
+                _ => None,
+            },
+        )
+    }
+}
+
+/// Items of an Accept header in lowercase, with their quality value
+///
+/// The parameters of the items are dropped, the items with an invalid quality too
+pub(crate) fn qualities(header: &str) -> Vec<(String, f32)> {
+    header
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';');
+            let name = params.next()?.trim().to_lowercase();
+
+            let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(quality) => quality.trim().parse().ok()?,
+                None => 1.0,
+            };
+
+            (!name.is_empty()).then_some((name, quality))
+        })
+        .collect()
+}
+
+/// Offered value with the best quality, the most specific matching range gives it
+///
+/// # Arguments
+///
+/// * header - Accept header of the request, every value is acceptable without it
+/// * offered - Values in order of preference of the server
+/// * specificity - Specificity of a range that matches a value
+fn preferred<'a>(
+    header: Option<&String>,
+    offered: &[&'a str],
+    specificity: impl Fn(&str, &str) -> Option<usize>,
+) -> Option<&'a str> {
+    let Some(header) = header else {
+        return offered.first().copied();
+    };
+
+    let qualities = qualities(header);
+    let mut best: Option<(&'a str, f32)> = None;
+
+    for value in offered {
+        // Parameters like `; charset=utf-8` are not compared
+        let name = value.split(';').next().unwrap_or_default().trim();
+        let name = name.to_lowercase();
+
+        let quality = qualities
+            .iter()
+            .filter_map(|(range, quality)| Some((specificity(range, &name)?, *quality)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, quality)| quality);
+
+        match quality {
+            Some(quality) if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) => {
+                best = Some((value, quality))
+            }
+            _ => {}
+        }
+    }
+
+    best.map(|(value, _)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &str) -> Request {
+        let request = format!("GET / HTTP/1.1\r\n{headers}\r\n\r\n");
+
+        Request::from_vec(&request.into_bytes()).unwrap()
+    }
+
+    #[test]
+    fn it_negotiates_the_media_type() {
+        let offered = ["application/json", "text/html"];
+
+        assert_eq!(
+            request("Accept: text/*;q=0.5, application/json;q=0.4").accepts(&offered),
+            Some("text/html")
+        );
+        assert_eq!(
+            request("Accept: */*, text/html;q=0").accepts(&offered),
+            Some("application/json")
+        );
+        assert_eq!(request("Accept: image/png").accepts(&offered), None);
+        assert_eq!(
+            request("Host: pillow").accepts(&offered),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn it_negotiates_the_language_and_the_charset() {
+        let request = request(
+            "Accept-Language: fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5\r\nAccept-Charset: iso-8859-1, utf-8;q=0.7",
+        );
+
+        assert_eq!(request.accepts_language(&["en-US", "fr"]), Some("fr"));
+        assert_eq!(request.accepts_language(&["de", "en-GB"]), Some("en-GB"));
+        assert_eq!(
+            request.accepts_charset(&["UTF-8", "ISO-8859-1"]),
+            Some("ISO-8859-1")
+        );
+        assert_eq!(request.accepts_charset(&["ascii"]), None);
+    }
+}
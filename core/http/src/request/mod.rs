@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::header::Header;
 use crate::http_methods::HttpMethods;
@@ -9,6 +10,13 @@ use crate::body::Body;
 
 use serde::{Deserialize, Serialize};
 
+mod accept;
+mod peer_certificate;
+
+pub(crate) use accept::qualities;
+
+pub use peer_certificate::PeerCertificate;
+
 /// Http Request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Request {
@@ -16,8 +24,11 @@ pub struct Request {
     version: String,
     headers: HashMap<Header, String>,
     uri: Uri,
+    query: Option<String>,
     params: HashMap<String, String>,
     body: Body,
+    server_name: Option<String>,
+    peer_certificate: Option<PeerCertificate>,
 }
 
 impl Default for Request {
@@ -34,8 +45,11 @@ impl Request {
             version: "HTTP/1.1".to_string(),
             headers: HashMap::new(),
             uri: Uri("".to_string()),
+            query: None,
             params: HashMap::new(),
             body: Body::NONE,
+            server_name: None,
+            peer_certificate: None,
         }
     }
 }
@@ -56,6 +70,11 @@ impl Request {
         &self.uri
     }
 
+    /// Query string as sent by the client, without the `?`
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
     /// Headers reference
     pub fn headers(&self) -> &HashMap<Header, String> {
         &self.headers
@@ -70,12 +89,104 @@ impl Request {
     pub fn body(&self) -> &Body {
         &self.body
     }
+
+    /// Server name sent by the client with TLS SNI
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// Client certificate verified with mutual TLS
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::Request;
+    ///
+    /// fn is_billing(request: &Request) -> bool {
+    ///     request
+    ///         .peer_certificate()
+    ///         .is_some_and(|cert| cert.sans.iter().any(|san| san == "DNS:billing.internal"))
+    /// }
+    /// ```
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.peer_certificate.as_ref()
+    }
+
+    /// Id of the last Server-Sent Event received by a reconnecting client
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.headers.get(&Header::LastEventId).map(|id| id.trim())
+    }
+
+    /// Whether the version cached by the client is the current one
+    ///
+    /// If-None-Match is checked first, If-Modified-Since only without it.
+    /// Only GET and HEAD requests can be answered with 304
+    ///
+    /// # Arguments
+    ///
+    /// * etag - Current ETag
+    /// * last_modified - Current modification date
+    pub fn is_fresh(&self, etag: &str, last_modified: Option<SystemTime>) -> bool {
+        if !matches!(self.method, HttpMethods::GET | HttpMethods::HEAD) {
+            return false;
+        }
+
+        // Weak comparison, W/"x" matches "x"
+        let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+
+        if let Some(tags) = self.headers.get(&Header::IfNoneMatch) {
+            return tags.trim() == "*" || tags.split(',').any(|tag| opaque(tag) == opaque(etag));
+        }
+
+        let since = self
+            .headers
+            .get(&Header::IfModifiedSince)
+            .and_then(|since| crate::date::parse_http_date(since));
+
+        match (since, last_modified) {
+            (Some(since), Some(last_modified)) => {
+                // The dates of the headers have no fraction of second
+                let seconds = last_modified
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs());
+
+                UNIX_EPOCH + Duration::from_secs(seconds) <= since
+            }
+            _ => false,
+        }
+    }
+
+    /// Host of the request: the SNI server name or the Host header without port
+    pub fn host(&self) -> Option<&str> {
+        match self.server_name() {
+            Some(server_name) => Some(server_name),
+            None => self.headers.get(&Header::Host).map(|host| {
+                // Keep IPv6 literals like [::1]:3000 intact
+                match host.rsplit_once(':') {
+                    Some((name, port)) if !name.ends_with(':') && port.parse::<u16>().is_ok() => {
+                        name
+                    }
+                    _ => host.as_str(),
+                }
+            }),
+        }
+    }
 }
 
 impl Request {
     pub fn add_params(&mut self, params: (String, String)) {
         self.params.insert(params.0, params.1);
     }
+
+    /// Set the TLS SNI server name of the connection
+    pub fn set_server_name(&mut self, server_name: Option<String>) {
+        self.server_name = server_name;
+    }
+
+    /// Set the client certificate verified with mutual TLS
+    pub fn set_peer_certificate(&mut self, peer_certificate: Option<PeerCertificate>) {
+        self.peer_certificate = peer_certificate;
+    }
 }
 
 impl Request {
@@ -87,12 +198,15 @@ impl Request {
     pub fn from_vec(data: &Vec<u8>) -> Result<Request, std::str::Utf8Error> {
         let request_str_full = Self::from_vec_to_str(data)?;
 
-        let mut req_vec: Vec<&str> = request_str_full.split("\n").collect();
+        let (head_str, body_str) = Self::separate_head_body(request_str_full);
+
+        let mut req_vec: Vec<&str> = head_str.split("\n").collect();
 
         let (method_str, uri_str, version_str) = Self::separate_method_uri_version(req_vec[0]);
 
         let method = crate::http_methods::from_str_to_http_method(method_str).unwrap();
         let uri = Self::get_uri(uri_str);
+        let query = uri_str.split_once('?').map(|(_, query)| query.to_string());
 
         let params = match Self::get_params(uri_str) {
             Some(hashmap) => hashmap,
@@ -100,40 +214,40 @@ impl Request {
         };
 
         let headers = Self::get_headers(&mut req_vec);
-        let body = Self::get_body(req_vec, headers.len());
+        let body = Self::get_body(body_str);
 
         Ok(Self {
             method,
             version: version_str.to_string(),
             uri,
+            query,
             headers,
             params,
             body,
+            server_name: None,
+            peer_certificate: None,
         })
     }
 
-    /// Create a body from a Vec<&str>
+    /// Separate the request line and headers from the body
     ///
     /// # Arguments
     ///
-    /// * header_vec - All header in a request
-    ///
-    /// * lenght - body lenght
-    fn get_body(headers_vec: Vec<&str>, lenght: usize) -> crate::body::Body {
-        let mut body_vec: Vec<Vec<&str>> = headers_vec.chunks(lenght).map(|x| x.into()).collect();
-        body_vec.remove(0);
-
-        let body_vec = body_vec[0].clone();
-
-        let mut body = String::new();
-
-        for body_items in &body_vec {
-            if !body_vec.is_empty() {
-                body = body + body_items;
-            }
+    /// * request_str - All the request
+    fn separate_head_body(request_str: &str) -> (&str, &str) {
+        match request_str.split_once("\r\n\r\n") {
+            Some(head_body) => head_body,
+            None => request_str.split_once("\n\n").unwrap_or((request_str, "")),
         }
+    }
 
-        let body = Self::remove_0(&body);
+    /// Create a body from the text after the headers
+    ///
+    /// # Arguments
+    ///
+    /// * body_str - Body of the request
+    fn get_body(body_str: &str) -> crate::body::Body {
+        let body = Self::remove_0(body_str);
 
         crate::body::from_string_to_body(body.to_string())
     }
@@ -143,7 +257,7 @@ impl Request {
     /// # Arguments
     ///
     /// * string - String where delete 0
-    fn remove_0(string: &String) -> &str {
+    fn remove_0(string: &str) -> &str {
         let vec_str: Vec<&str> = string.split("\0").collect();
 
         vec_str[0]
@@ -156,18 +270,11 @@ impl Request {
         let mut header_hash_map = HashMap::new();
 
         for header in headers_vec {
-            let key_value_vec: Vec<&str> = header.split(":").map(|x| x.trim()).collect();
-
-            if key_value_vec.len() > 1 {
-                let key = crate::header::from_string_to_header(key_value_vec[0].to_string());
-                let value = key_value_vec[1].to_string();
+            // Values like `Host: localhost:3000` keep their colons
+            if let Some((key, value)) = header.split_once(':') {
+                let key = crate::header::from_string_to_header(key.trim().to_string());
 
-                if key_value_vec.len() > 2 {
-                    let value = value + key_value_vec[2];
-                    header_hash_map.insert(key, value);
-                } else {
-                    header_hash_map.insert(key, value);
-                }
+                header_hash_map.insert(key, value.trim().to_string());
             }
         }
 
@@ -0,0 +1,104 @@
+use std::{fmt::Write, io};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+/// Client certificate verified during the TLS handshake
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerCertificate {
+    /// Distinguished name, like `CN=billing, O=Example`
+    pub subject: String,
+
+    /// Subject alternative names, like `DNS:billing.internal` or `URI:spiffe://example/billing`
+    pub sans: Vec<String>,
+
+    /// SHA-256 of the DER certificate in lowercase hex
+    pub fingerprint: String,
+}
+
+impl PeerCertificate {
+    /// Subject, SANs and fingerprint of a DER certificate
+    pub fn from_der(der: &[u8]) -> io::Result<Self> {
+        let (_, cert) = X509Certificate::from_der(der).map_err(invalid_data)?;
+
+        let sans = match cert.subject_alternative_name().map_err(invalid_data)? {
+            Some(extension) => extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(general_name)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let fingerprint =
+            Sha256::digest(der)
+                .iter()
+                .fold(String::with_capacity(64), |mut hex, byte| {
+                    let _ = write!(hex, "{byte:02x}");
+                    hex
+                });
+
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            sans,
+            fingerprint,
+        })
+    }
+}
+
+/// SAN in the OpenSSL text form
+fn general_name(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(dns) => Some(format!("DNS:{dns}")),
+        GeneralName::RFC822Name(email) => Some(format!("email:{email}")),
+        GeneralName::URI(uri) => Some(format!("URI:{uri}")),
+        GeneralName::IPAddress(ip) => match ip.len() {
+            4 => Some(format!(
+                "IP:{}",
+                std::net::Ipv4Addr::from(<[u8; 4]>::try_from(*ip).ok()?)
+            )),
+            16 => Some(format!(
+                "IP:{}",
+                std::net::Ipv6Addr::from(<[u8; 16]>::try_from(*ip).ok()?)
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_subject_and_the_sans() {
+        let mut params = rcgen::CertificateParams::new(vec![
+            "billing.internal".to_string(),
+            "127.0.0.1".to_string(),
+        ])
+        .unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "billing");
+
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+
+        let peer = PeerCertificate::from_der(cert.der()).unwrap();
+
+        assert_eq!(peer.subject, "CN=billing");
+        assert_eq!(peer.sans, vec!["DNS:billing.internal", "IP:127.0.0.1"]);
+        assert_eq!(peer.fingerprint.len(), 64);
+        assert!(PeerCertificate::from_der(b"not a certificate").is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Compression of the responses with the encodings of Accept-Encoding
+
+use std::io::{self, Write};
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+
+use super::{
+    header::Header,
+    status_code::{StatusCode, Successfull},
+    Body, Response,
+};
+use crate::Request;
+
+/// Content coding of a body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// Name in Accept-Encoding and Content-Encoding
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    /// Extension of the precompressed files, like `app.js.br`
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Encoding::Brotli => Some("br"),
+            Encoding::Zstd => Some("zst"),
+            Encoding::Gzip => Some("gz"),
+            Encoding::Deflate => None,
+        }
+    }
+
+    /// Compress a body with the default level of the encoding
+    pub fn encode(&self, content: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut encoded = Vec::new();
+                let params = brotli::enc::BrotliEncoderParams {
+                    quality: 5,
+                    ..Default::default()
+                };
+
+                brotli::BrotliCompress(&mut &content[..], &mut encoded, &params)?;
+                Ok(encoded)
+            }
+            Encoding::Zstd => zstd::encode_all(content, 0),
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// Encoding of `encodings` preferred by the Accept-Encoding of a request
+    ///
+    /// The quality values of the client come first, then the order of `encodings`
+    pub fn negotiate(request: &Request, encodings: &[Encoding]) -> Option<Encoding> {
+        let accept = request.headers().get(&Header::AcceptEncoding)?;
+
+        let qualities = crate::request::qualities(accept);
+
+        let quality = |name: &str| {
+            qualities
+                .iter()
+                .find(|(coding, _)| coding == name || (name == "gzip" && coding == "x-gzip"))
+                .or_else(|| qualities.iter().find(|(coding, _)| coding == "*"))
+                .map(|(_, quality)| *quality)
+        };
+
+        let mut preferred: Option<(Encoding, f32)> = None;
+
+        for encoding in encodings {
+            match quality(encoding.as_str()) {
+                Some(quality)
+                    if quality > 0.0 && preferred.is_none_or(|(_, best)| quality > best) =>
+                {
+                    preferred = Some((*encoding, quality))
+                }
+                _ => {}
+            }
+        }
+
+        preferred.map(|(encoding, _)| encoding)
+    }
+}
+
+/// Compression of the responses with a body in memory
+///
+/// The streamed bodies, like the files and the Server-Sent Events, are sent as they are
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_http::compression::Compression;
+///
+/// let compression = Compression {
+///     min_size: 512,
+///     ..Compression::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compression {
+    /// Encodings used, in order of preference
+    pub encodings: Vec<Encoding>,
+
+    /// Bodies of less bytes are not compressed
+    pub min_size: usize,
+
+    /// Content types compressed, `text/` is every text type
+    pub content_types: Vec<String>,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            encodings: vec![
+                Encoding::Brotli,
+                Encoding::Zstd,
+                Encoding::Gzip,
+                Encoding::Deflate,
+            ],
+            min_size: 1024,
+            content_types: [
+                "text/",
+                "application/javascript",
+                "application/js",
+                "application/json",
+                "application/wasm",
+                "application/xml",
+                "image/svg+xml",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+impl Compression {
+    /// Response compressed with the encoding preferred by the request
+    ///
+    /// The responses that could be compressed get `Vary: Accept-Encoding`,
+    /// and the ETag of a compressed body is weak
+    pub fn compress(&self, request: &Request, mut response: Response) -> Response {
+        if !self.is_compressible(&response) {
+            return response;
+        }
+
+        add_vary(&mut response, "Accept-Encoding");
+
+        let Some(encoding) = Encoding::negotiate(request, &self.encodings) else {
+            return response;
+        };
+
+        let content = match encoding.encode(response.content.as_bytes()) {
+            Ok(content) if content.len() < response.content.as_bytes().len() => content,
+            _ => return response,
+        };
+
+        let weak_etag = response
+            .get_header(&Header::ETag)
+            .filter(|etag| !etag.starts_with("W/"))
+            .map(|etag| format!("W/{etag}"));
+
+        if let Some(etag) = weak_etag {
+            response.add_header(Header::ETag, etag);
+        }
+
+        response.add_multiple_headers(vec![
+            (Header::ContentEncoding, encoding.as_str().to_string()),
+            (Header::ContentLength, content.len().to_string()),
+        ]);
+        response.content = Body::BYTES(content);
+
+        response
+    }
+
+    fn is_compressible(&self, response: &Response) -> bool {
+        let successful = matches!(
+            response.get_status_code(),
+            StatusCode::Successfull(status) if *status != Successfull::PartialContent
+        );
+
+        let no_transform = response
+            .get_header(&Header::CacheControl)
+            .is_some_and(|cache_control| cache_control.to_lowercase().contains("no-transform"));
+
+        let content_type = response
+            .get_header(&Header::ContentType)
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|content_type| content_type.trim().to_lowercase());
+
+        let allowed = content_type.is_some_and(|content_type| {
+            self.content_types
+                .iter()
+                .any(|allowed| match allowed.ends_with('/') {
+                    true => content_type.starts_with(allowed.as_str()),
+                    false => content_type == *allowed,
+                })
+        });
+
+        successful
+            && allowed
+            && !no_transform
+            && response.get_header(&Header::ContentEncoding).is_none()
+            && !matches!(response.content, Body::STREAM(_))
+            && response.content.as_bytes().len() >= self.min_size
+    }
+}
+
+/// Add a header name to the Vary of a response
+pub(super) fn add_vary(response: &mut Response, name: &str) {
+    let vary = match response.get_header(&Header::Vary) {
+        None => name.to_string(),
+        Some(vary)
+            if vary
+                .split(',')
+                .any(|value| value.trim() == "*" || value.trim().eq_ignore_ascii_case(name)) =>
+        {
+            return
+        }
+        Some(vary) => format!("{vary}, {name}"),
+    };
+
+    response.add_header(Header::Vary, vary);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn request(accept_encoding: &str) -> Request {
+        let request = format!("GET / HTTP/1.1\r\nAccept-Encoding: {accept_encoding}\r\n\r\n");
+
+        Request::from_vec(&request.into_bytes()).unwrap()
+    }
+
+    #[test]
+    fn it_negotiates_the_encoding() {
+        let all = Compression::default().encodings;
+
+        assert_eq!(
+            Encoding::negotiate(&request("gzip, br"), &all),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(
+            Encoding::negotiate(&request("gzip;q=1, br;q=0.5"), &all),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(
+            Encoding::negotiate(&request("*;q=0.1, br;q=0"), &all),
+            Some(Encoding::Zstd)
+        );
+        assert_eq!(Encoding::negotiate(&request("identity"), &all), None);
+    }
+
+    #[test]
+    fn it_compresses_the_allowed_responses() {
+        let compression = Compression {
+            encodings: vec![Encoding::Gzip],
+            ..Compression::default()
+        };
+        let text = "pillow ".repeat(500);
+
+        let mut response = Response::text(&text);
+        response.add_header(Header::ETag, "\"v1\"".to_string());
+        let response = compression.compress(&request("gzip"), response);
+
+        assert_eq!(response.get_header(&Header::ContentEncoding), Some("gzip"));
+        assert_eq!(response.get_header(&Header::Vary), Some("Accept-Encoding"));
+        assert_eq!(response.get_header(&Header::ETag), Some("W/\"v1\""));
+
+        let mut decoded = String::new();
+        GzDecoder::new(response.get_body().as_bytes())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+
+        let small = compression.compress(&request("gzip"), Response::text("pillow"));
+        assert_eq!(small.get_header(&Header::ContentEncoding), None);
+
+        let mut image = Response::text(&text);
+        image.add_header(Header::ContentType, "image/png".to_string());
+        let image = compression.compress(&request("gzip"), image);
+        assert_eq!(image.get_header(&Header::Vary), None);
+    }
+}
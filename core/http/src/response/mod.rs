@@ -1,7 +1,14 @@
 use crate::header::{self, ContentType};
+pub mod compression;
+mod range;
+pub mod sse;
+pub mod static_dir;
 pub mod static_files;
 pub mod status_code;
+mod stream;
+
 use serde::{Deserialize, Serialize};
+pub use stream::BodyStream;
 
 /// Response struct to client
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -25,19 +32,24 @@ pub struct Response {
 pub enum Body {
     STRING(String),
     BYTES(Vec<u8>),
+
+    /// Bytes shared with a cache, the responses don't copy them
+    SHARED(#[serde(with = "shared_bytes")] Arc<[u8]>),
+
+    /// Sent by chunks while it's produced
+    #[serde(skip)]
+    STREAM(BodyStream),
 }
 
 impl std::fmt::Display for Body {
+    /// Text of the body, the bytes that are not UTF-8 are replaced.
+    /// Use `as_bytes` to send the body
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Body::STRING(string) => write!(f, "{}", string),
-            Body::BYTES(bytes) => {
-                let bytes_str = bytes
-                    .iter()
-                    .map(|&byte| format!("{:02X}", byte))
-                    .collect::<String>();
-                write!(f, "{}", bytes_str)
-            }
+            Body::BYTES(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            Body::SHARED(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            Body::STREAM(_) => Ok(()),
         }
     }
 }
@@ -48,17 +60,41 @@ impl Body {
             Body::STRING(s) => s.as_bytes(),
 
             Body::BYTES(b) => b.as_slice(),
+            Body::SHARED(b) => b,
+
+            Body::STREAM(_) => &[],
         }
     }
 }
 
-use std::collections::HashMap;
+/// Serde of the shared bytes, like the other bytes
+mod shared_bytes {
+    use std::sync::Arc;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        bytes: &Arc<[u8]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bytes.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<[u8]>, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Arc::from)
+    }
+}
+
+use std::{collections::HashMap, sync::Arc};
 
-use chrono;
 use serde_json::Value;
 
 use pillow_templates::Template;
 
+use crate::Request;
+
 use self::{
     header::Header,
     status_code::{AsStr, StatusCode},
@@ -236,14 +272,12 @@ impl Response {
 
         response.add_multiple_headers(vec![
             (Header::AccessControlAllowOrigin, response.cors.to_string()),
-            (Header::AcceptRanges, "bytes".to_string()),
             (Header::ContentLength, json.len().to_string()),
             (
                 Header::ContentType,
                 "application/json; charset=utf-8".to_string(),
             ),
             (Header::Date, date.to_string()),
-            (Header::Date, date.to_string()),
             (Header::Vary, "Accept-Encoding".to_string()),
         ]);
 
@@ -280,14 +314,12 @@ impl Response {
 
         response.add_multiple_headers(vec![
             (Header::AccessControlAllowOrigin, response.cors.to_string()),
-            (Header::AcceptRanges, "bytes".to_string()),
             (Header::ContentLength, js.len().to_string()),
             (
                 Header::ContentType,
                 "application/json; charset=utf-8".to_string(),
             ),
             (Header::Date, date.to_string()),
-            (Header::Date, date.to_string()),
             (Header::Vary, "Accept-Encoding".to_string()),
         ]);
 
@@ -297,6 +329,52 @@ impl Response {
         response
     }
 
+    /// JSON or html page of the same data, the one preferred by the Accept of the request
+    ///
+    /// JSON is sent when the client has no preference, and 406 when it accepts neither
+    ///
+    /// # Arguments
+    ///
+    /// * request - Request with the Accept header
+    /// * data - Value sent as JSON
+    /// * view - Template of the page, only rendered for html
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::{json, Request, Response};
+    /// use pillow_templates::Template;
+    ///
+    /// let request = b"GET /users HTTP/1.1\r\nAccept: application/json\r\n\r\n".to_vec();
+    /// let request = Request::from_vec(&request).unwrap();
+    ///
+    /// let response = Response::negotiate(&request, json!({ "name": "foo" }), |data| {
+    ///     Template::Handlebars("users", data.clone())
+    /// });
+    /// ```
+    pub fn negotiate(
+        request: &Request,
+        data: Value,
+        view: impl FnOnce(&Value) -> Template,
+    ) -> Response {
+        let mut response = match request.accepts(&["application/json", "text/html"]) {
+            Some("text/html") => Response::view(view(&data)),
+            Some(_) => Response::json(data),
+            None => {
+                let mut response = Response::new_empty();
+
+                response.set_status_code(StatusCode::ClientError(
+                    status_code::ClientError::NotAcceptable,
+                ));
+                response
+            }
+        };
+
+        compression::add_vary(&mut response, "Accept");
+
+        response
+    }
+
     /// Send text to client
     ///
     /// # Examples
@@ -329,17 +407,15 @@ impl Response {
     pub fn css(&mut self, css: String) -> String {
         let status_line = self.get_status_line();
 
-        let date = chrono::offset::Local::now();
+        let date = crate::get_date_now!();
 
         self.add_multiple_headers(vec![
             (Header::AccessControlAllowOrigin, self.cors.to_string()),
             (Header::ContentLength, css.len().to_string()),
-            // ("Content-Encoding", "br".to_string());
             (Header::ContentType, "text/css; charset=utf-8".to_string()),
             (Header::Date, date.to_string()),
             (Header::LastModified, date.to_string()),
             // ("Transfer-Encoding", "chunked".to_string()),
-            // ("Vary", "Accept-Encoding".to_string()),
         ]);
 
         let headers = self.get_headers();
@@ -352,12 +428,11 @@ impl Response {
     pub fn javascript(&mut self, js: String) -> String {
         let status_line = self.get_status_line();
 
-        let date = chrono::offset::Local::now();
+        let date = crate::get_date_now!();
 
         self.add_multiple_headers(vec![
             (Header::AccessControlAllowOrigin, self.cors.to_string()),
             (Header::ContentLength, js.len().to_string()),
-            // ("Content-Encoding", "gzip".to_string()),
             (
                 Header::ContentType,
                 "application/javascript; charset=utf-8".to_string(),
@@ -365,7 +440,6 @@ impl Response {
             (Header::Date, date.to_string()),
             (Header::LastModified, date.to_string()),
             // ("Transfer-Encoding", String::from("chunked")),
-            // ("Vary", "Accept-Encoding".to_string())
         ]);
 
         let headers = self.get_headers();
@@ -411,16 +485,36 @@ impl Response {
 }
 
 impl ToString for Response {
-    /// Convert Response struct in String
+    /// Convert Response struct in String, see `to_bytes` for binary bodies
     fn to_string(&self) -> String {
-        let res = format!(
-            "{}{}\r\n\r\n{}",
-            &self.get_status_line(),
-            &self.get_headers(),
-            &self.content
-        );
+        String::from_utf8_lossy(&self.to_bytes()).into_owned()
+    }
+}
 
-        res
+impl Response {
+    /// Status line, headers and body of the Response as sent to the client
+    ///
+    /// A streamed body is not included, the server sends it by chunks
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::{header::ContentType, Response};
+    ///
+    /// let response = Response::file(ContentType::ImgPng, vec![0x89, b'P', b'N', b'G']);
+    ///
+    /// assert!(response.to_bytes().ends_with(&[0x89, b'P', b'N', b'G']));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.head().into_bytes();
+        bytes.extend_from_slice(self.content.as_bytes());
+
+        bytes
+    }
+
+    /// Status line and headers, with the empty line that ends them
+    pub fn head(&self) -> String {
+        format!("{}{}\r\n\r\n", self.get_status_line(), self.get_headers())
     }
 }
 
@@ -444,26 +538,61 @@ impl Response {
 }
 
 impl Response {
-    pub fn websocket_upgrade_connection() -> Response {
+    /// 101 response accepting the WebSocket upgrade of the request
+    ///
+    /// A request without a valid Sec-WebSocket-Key gets a 400 and a
+    /// Sec-WebSocket-Version other than 13 gets a 426
+    ///
+    /// # Arguments
+    ///
+    /// * request - Upgrade request
+    /// * protocol - Subprotocol chosen with `websocket::negotiate_protocol`
+    pub fn websocket_upgrade_connection(request: &Request, protocol: Option<&str>) -> Response {
         let mut response = Response::new_empty();
+        response.clear_headers();
+
+        if request
+            .headers()
+            .get(&Header::SecWebSocketVersion)
+            .map(|v| v.trim())
+            != Some("13")
+        {
+            response.set_status_code(StatusCode::ClientError(
+                status_code::ClientError::UpgradeRequired,
+            ));
+            response.add_header(Header::SecWebSocketVersion, "13".to_string());
+
+            return response;
+        }
+
+        let Some(key) = request
+            .headers()
+            .get(&Header::SecWebSocketKey)
+            .filter(|key| crate::websocket::is_valid_key(key))
+        else {
+            response.set_status_code(StatusCode::ClientError(
+                status_code::ClientError::BadRequest,
+            ));
+
+            return response;
+        };
 
         response.set_status_code(StatusCode::Information(
             status_code::Information::SwitchingProtocols,
         ));
 
-        response.clear_headers();
-
         response.add_multiple_headers(vec![
             (Header::Upgrade, "websocket".to_string()),
             (Header::Connection, "Upgrade".to_string()),
             (
                 Header::SecWebSocketAccept,
-                "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string(),
+                crate::websocket::accept_key(key),
             ),
-            (Header::SecWebSocketProtocol, "superchat".to_string()),
         ]);
 
-        println!("Response: {:#?}", &response.to_string());
+        if let Some(protocol) = protocol {
+            response.add_header(Header::SecWebSocketProtocol, protocol.to_string());
+        }
 
         response
     }
@@ -536,6 +665,11 @@ impl Response {
         res
     }
 
+    /// Value of a header of the response
+    pub fn get_header(&self, header: &Header) -> Option<&str> {
+        self.headers.get(header).map(String::as_str)
+    }
+
     /// Clear All headers
     pub fn clear_headers(&mut self) {
         self.headers = HashMap::new();
@@ -558,6 +692,11 @@ impl Response {
         status_line
     }
 
+    /// Status Code reference
+    pub fn get_status_code(&self) -> &StatusCode {
+        &self.status_code
+    }
+
     /// Set Status Code Like 200 OK
     ///
     /// # Examples
@@ -575,11 +714,78 @@ impl Response {
     }
 }
 
+/// Current date of the Date header, see `date::now`
 #[macro_export]
 macro_rules! get_date_now {
     () => {{
-        let date = chrono::offset::Local::now();
-
-        date
+        $crate::date::now()
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_serializes_binary_bodies_losslessly() {
+        let bytes = vec![0x00, 0xFF, 0xD8, b'\r', b'\n'];
+        let response = Response::file(ContentType::ImgJpeg, bytes.clone());
+
+        let serialized = response.to_bytes();
+
+        assert!(serialized.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(serialized.ends_with(&[b"\r\n\r\n".as_slice(), &bytes].concat()));
+        assert_eq!(Body::BYTES(b"font".to_vec()).to_string(), "font");
+    }
+
+    #[test]
+    fn it_negotiates_json_or_html() {
+        let request = |accept: &str| {
+            let request = format!("GET / HTTP/1.1\r\nAccept: {accept}\r\n\r\n");
+            Request::from_vec(&request.into_bytes()).unwrap()
+        };
+        let view = |_: &Value| -> Template { panic!("the page is not rendered") };
+
+        let json = Response::negotiate(&request("application/*"), serde_json::json!([1]), view);
+        assert_eq!(json.get_body().to_string(), "[1]");
+        assert_eq!(json.get_header(&Header::AcceptRanges), None);
+        assert_eq!(
+            json.get_header(&Header::Vary),
+            Some("Accept-Encoding, Accept")
+        );
+
+        let image = Response::negotiate(&request("image/png"), serde_json::json!([1]), view);
+        assert_eq!(
+            image.get_status_code(),
+            &StatusCode::ClientError(status_code::ClientError::NotAcceptable)
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_websocket_keys() {
+        let upgrade = |key: &str| {
+            let request = format!(
+                "GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {key}\r\n\r\n"
+            );
+            let request = Request::from_vec(&request.into_bytes()).unwrap();
+
+            Response::websocket_upgrade_connection(&request, None)
+                .get_status_code()
+                .clone()
+        };
+
+        assert_eq!(
+            upgrade("dGhlIHNhbXBsZSBub25jZQ=="),
+            StatusCode::Information(status_code::Information::SwitchingProtocols)
+        );
+        assert_eq!(
+            upgrade("c2hvcnQ="),
+            StatusCode::ClientError(status_code::ClientError::BadRequest)
+        );
+        assert_eq!(
+            upgrade("not base64!"),
+            StatusCode::ClientError(status_code::ClientError::BadRequest)
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Range requests, for seeking in videos and resuming downloads
+
+use std::{
+    io,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{stream, StreamExt};
+
+use super::{
+    header::Header,
+    status_code::{ClientError, StatusCode, Successfull},
+    stream::file_chunks,
+    Body, BodyStream, Response,
+};
+use crate::{http_methods::HttpMethods, Request};
+
+/// Most ranges served to a request, the whole content is sent to more
+const MAX_RANGES: usize = 32;
+
+/// Bytes from start to end, both included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn content_range(&self, len: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, len)
+    }
+}
+
+/// Part of a content asked by a request
+#[derive(Debug, PartialEq, Eq)]
+enum Ranges {
+    /// No Range header or one that can't be used
+    Full,
+
+    Partial(Vec<ByteRange>),
+
+    /// None of the ranges is in the content
+    Unsatisfiable,
+}
+
+impl Ranges {
+    /// Ranges asked by a request for a content of `len` bytes
+    ///
+    /// If-Range is compared with the ETag or the Last-Modified of the content,
+    /// the whole content is sent when it changed
+    fn of(request: &Request, len: u64, etag: Option<&str>, last_modified: Option<&str>) -> Ranges {
+        if *request.method() != HttpMethods::GET {
+            return Ranges::Full;
+        }
+
+        let Some(range) = request.headers().get(&Header::Range) else {
+            return Ranges::Full;
+        };
+
+        if let Some(if_range) = request.headers().get(&Header::IfRange) {
+            let if_range = Some(if_range.trim());
+
+            // Only strong ETags are compared, a weak one starts with W/
+            let unchanged = match if_range {
+                Some(tag) if tag.starts_with('"') => etag == if_range,
+                _ => last_modified.is_some() && last_modified == if_range,
+            };
+
+            if !unchanged {
+                return Ranges::Full;
+            }
+        }
+
+        Self::parse(range, len)
+    }
+
+    fn parse(range: &str, len: u64) -> Ranges {
+        let range = range.trim();
+
+        let Some(specs) = range
+            .get(..6)
+            .filter(|unit| unit.eq_ignore_ascii_case("bytes="))
+            .map(|_| &range[6..])
+        else {
+            return Ranges::Full;
+        };
+
+        let number = |value: &str| {
+            let value = value.trim();
+
+            if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+
+            value.parse::<u64>().ok()
+        };
+
+        let mut ranges = Vec::new();
+        let mut count = 0;
+
+        for spec in specs
+            .split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+        {
+            let Some((start, end)) = spec.split_once('-') else {
+                return Ranges::Full;
+            };
+
+            let range = match (start.trim(), end.trim()) {
+                ("", suffix) => match number(suffix) {
+                    Some(0) => None,
+                    Some(suffix) => (len > 0).then(|| ByteRange {
+                        start: len - suffix.min(len),
+                        end: len - 1,
+                    }),
+                    None => return Ranges::Full,
+                },
+                (start, "") => match number(start) {
+                    Some(start) => (start < len).then(|| ByteRange {
+                        start,
+                        end: len - 1,
+                    }),
+                    None => return Ranges::Full,
+                },
+                (start, end) => match (number(start), number(end)) {
+                    (Some(start), Some(end)) if start <= end => (start < len).then(|| ByteRange {
+                        start,
+                        end: end.min(len - 1),
+                    }),
+                    _ => return Ranges::Full,
+                },
+            };
+
+            count += 1;
+            ranges.extend(range);
+        }
+
+        match count {
+            0 => Ranges::Full,
+            count if count > MAX_RANGES => Ranges::Full,
+            _ if ranges.is_empty() => Ranges::Unsatisfiable,
+            _ => Ranges::Partial(ranges),
+        }
+    }
+}
+
+/// Boundary of a multipart/byteranges body
+fn boundary() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+
+    format!(
+        "pillow-{:08x}{:016x}",
+        nanos,
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Parts of a multipart/byteranges body: the head of each range and the end
+struct Multipart {
+    heads: Vec<Vec<u8>>,
+    end: Vec<u8>,
+}
+
+impl Multipart {
+    fn new(ranges: &[ByteRange], len: u64, content_type: Option<&str>, boundary: &str) -> Self {
+        let heads = ranges
+            .iter()
+            .map(|range| {
+                let mut head = format!("\r\n--{boundary}\r\n");
+
+                if let Some(content_type) = content_type {
+                    head.push_str(&format!("Content-Type: {content_type}\r\n"));
+                }
+
+                head.push_str(&format!(
+                    "Content-Range: {}\r\n\r\n",
+                    range.content_range(len)
+                ));
+                head.into_bytes()
+            })
+            .collect();
+
+        Self {
+            heads,
+            end: format!("\r\n--{boundary}--\r\n").into_bytes(),
+        }
+    }
+
+    /// Length of the body with the ranges
+    fn len(&self, ranges: &[ByteRange]) -> u64 {
+        let heads: usize = self.heads.iter().map(Vec::len).sum();
+        let ranges: u64 = ranges.iter().map(ByteRange::len).sum();
+
+        heads as u64 + ranges + self.end.len() as u64
+    }
+}
+
+impl Response {
+    /// Part of the body asked by the Range header of a request
+    ///
+    /// Answers 206 with a range or a multipart/byteranges body, and 416
+    /// when no range is in the body. If-Range is compared with the ETag
+    /// and Last-Modified of the response. Only 200 responses with the body
+    /// in memory are cut, like the ones of `Response::file`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::{header::ContentType, Request, Response};
+    ///
+    /// fn video(request: &Request) -> Response {
+    ///     Response::file(ContentType::MP4, vec![0; 1024]).range(request)
+    /// }
+    /// ```
+    pub fn range(mut self, request: &Request) -> Response {
+        if self.status_code != StatusCode::Successfull(Successfull::OK)
+            || matches!(self.content, Body::STREAM(_))
+        {
+            return self;
+        }
+
+        let len = self.content.as_bytes().len() as u64;
+        let ranges = Ranges::of(
+            request,
+            len,
+            self.get_header(&Header::ETag),
+            self.get_header(&Header::LastModified),
+        );
+
+        self.add_header(Header::AcceptRanges, "bytes".to_string());
+
+        let content = match ranges {
+            Ranges::Full => return self,
+            Ranges::Unsatisfiable => {
+                self.unsatisfiable(len);
+                return self;
+            }
+            Ranges::Partial(ranges) => {
+                let multipart = self.partial(&ranges, len);
+                let bytes = self.content.as_bytes();
+                let slice = |range: &ByteRange| &bytes[range.start as usize..=range.end as usize];
+
+                match multipart {
+                    None => slice(&ranges[0]).to_vec(),
+                    Some(multipart) => {
+                        let mut content = Vec::new();
+
+                        for (head, range) in multipart.heads.iter().zip(&ranges) {
+                            content.extend_from_slice(head);
+                            content.extend_from_slice(slice(range));
+                        }
+
+                        content.extend_from_slice(&multipart.end);
+                        content
+                    }
+                }
+            }
+        };
+
+        self.add_header(Header::ContentLength, content.len().to_string());
+        self.content = Body::BYTES(content);
+        self
+    }
+
+    /// File or the parts of it asked by a request, see `Response::range`
+    ///
+    /// The parts are read from the disk when they are sent
+    pub(crate) fn send_file_range(
+        path: &Path,
+        content_type: &str,
+        request: &Request,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+    ) -> io::Result<Response> {
+        let file = std::fs::File::open(path)?;
+        let metadata = file.metadata()?;
+
+        if !metadata.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Not a file"));
+        }
+
+        let len = metadata.len();
+        let mut response = Response::new_empty();
+
+        response.add_multiple_headers(vec![
+            (Header::AcceptRanges, "bytes".to_string()),
+            (Header::ContentType, content_type.to_string()),
+        ]);
+
+        let body = match Ranges::of(request, len, etag, last_modified) {
+            Ranges::Full => BodyStream::from_file(file)?,
+            Ranges::Unsatisfiable => {
+                response.unsatisfiable(len);
+                return Ok(response);
+            }
+            Ranges::Partial(ranges) => match response.partial(&ranges, len) {
+                None => BodyStream::with_len(
+                    file_chunks(file, ranges[0].start, ranges[0].len()),
+                    ranges[0].len(),
+                ),
+                Some(multipart) => {
+                    let body_len = multipart.len(&ranges);
+                    let mut parts = Vec::new();
+
+                    for (head, range) in multipart.heads.into_iter().zip(ranges) {
+                        let chunks = file_chunks(file.try_clone()?, range.start, range.len());
+                        parts.push(stream::once(async { Ok(head) }).chain(chunks).boxed());
+                    }
+
+                    let end = multipart.end;
+                    let chunks = stream::iter(parts)
+                        .flatten()
+                        .chain(stream::once(async { Ok(end) }));
+
+                    BodyStream::with_len(chunks, body_len)
+                }
+            },
+        };
+
+        response.content = Body::STREAM(body);
+        Ok(response)
+    }
+
+    /// 416 with the length of the content
+    fn unsatisfiable(&mut self, len: u64) {
+        self.set_status_code(StatusCode::ClientError(ClientError::RangeNotSatisfiable));
+        self.add_header(Header::ContentRange, format!("bytes */{len}"));
+        self.headers.remove(&Header::ContentType);
+        self.content = Body::STRING(String::new());
+    }
+
+    /// 206 with the Content-Range of a range or the parts of several ranges
+    fn partial(&mut self, ranges: &[ByteRange], len: u64) -> Option<Multipart> {
+        self.set_status_code(StatusCode::Successfull(Successfull::PartialContent));
+
+        if let [range] = ranges {
+            self.add_header(Header::ContentRange, range.content_range(len));
+            return None;
+        }
+
+        let boundary = boundary();
+        let multipart = Multipart::new(
+            ranges,
+            len,
+            self.get_header(&Header::ContentType),
+            &boundary,
+        );
+
+        self.add_header(
+            Header::ContentType,
+            format!("multipart/byteranges; boundary={boundary}"),
+        );
+
+        Some(multipart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::ContentType;
+
+    fn request(headers: &str) -> Request {
+        Request::from_vec(&format!("GET / HTTP/1.1\r\n{headers}\r\n\r\n").into_bytes()).unwrap()
+    }
+
+    #[test]
+    fn it_parses_ranges() {
+        let range = |start, end| ByteRange { start, end };
+
+        assert_eq!(
+            Ranges::parse("bytes=0-1, 5-, -3", 10),
+            Ranges::Partial(vec![range(0, 1), range(5, 9), range(7, 9)])
+        );
+        assert_eq!(
+            Ranges::parse("bytes=4-100", 10),
+            Ranges::Partial(vec![range(4, 9)])
+        );
+        assert_eq!(Ranges::parse("bytes=10-, -0", 10), Ranges::Unsatisfiable);
+        assert_eq!(Ranges::parse("bytes=5-4", 10), Ranges::Full);
+        assert_eq!(Ranges::parse("items=0-1", 10), Ranges::Full);
+    }
+
+    #[test]
+    fn it_cuts_the_body() {
+        let file = || Response::file(ContentType::Text, b"hello world".to_vec());
+
+        let response = file().range(&request("Range: bytes=6-"));
+        assert_eq!(
+            response.get_status_code(),
+            &StatusCode::Successfull(Successfull::PartialContent)
+        );
+        assert_eq!(
+            response.get_header(&Header::ContentRange),
+            Some("bytes 6-10/11")
+        );
+        assert_eq!(response.get_body().as_bytes(), b"world");
+
+        let response = file().range(&request("Range: bytes=0-0,-1"));
+        let content_type = response.get_header(&Header::ContentType).unwrap();
+        let boundary = content_type.split("boundary=").nth(1).unwrap();
+        assert_eq!(
+            String::from_utf8(response.get_body().as_bytes().to_vec()).unwrap(),
+            format!(
+                "\r\n--{boundary}\r\nContent-Type: {}\r\nContent-Range: bytes 0-0/11\r\n\r\nh\
+                 \r\n--{boundary}\r\nContent-Type: {}\r\nContent-Range: bytes 10-10/11\r\n\r\nd\
+                 \r\n--{boundary}--\r\n",
+                ContentType::Text.as_str(),
+                ContentType::Text.as_str()
+            )
+        );
+
+        let response = file().range(&request("Range: bytes=20-"));
+        assert_eq!(
+            response.get_header(&Header::ContentRange),
+            Some("bytes */11")
+        );
+
+        let mut response = file();
+        response.add_header(Header::ETag, "\"v2\"".to_string());
+        let response = response.range(&request("Range: bytes=0-1\r\nIf-Range: \"v1\""));
+        assert_eq!(response.get_body().as_bytes(), b"hello world");
+    }
+
+    #[tokio::test]
+    async fn it_streams_the_ranges_of_files() {
+        let path = std::env::temp_dir().join(format!("pillow-range-{}.txt", std::process::id()));
+        std::fs::write(&path, "hello world").unwrap();
+
+        let response = Response::send_file_range(
+            &path,
+            "text/plain",
+            &request("Range: bytes=0-1,6-7"),
+            None,
+            None,
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let Body::STREAM(body) = response.get_body() else {
+            panic!("file body is not a stream");
+        };
+        let chunks: Vec<_> = body.take().unwrap().map(Result::unwrap).collect().await;
+        let content = String::from_utf8(chunks.concat()).unwrap();
+
+        assert_eq!(body.content_length(), Some(content.len() as u64));
+        assert!(content.contains("Content-Range: bytes 0-1/11\r\n\r\nhe\r\n"));
+        assert!(content.contains("Content-Range: bytes 6-7/11\r\n\r\nwo\r\n"));
+    }
+}
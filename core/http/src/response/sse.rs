@@ -0,0 +1,172 @@
+//! Server-Sent Events
+//!
+//! ```rust
+//! use futures::stream;
+//! use pillow_http::{sse::Event, Request, Response};
+//!
+//! fn events(request: &Request) -> Response {
+//!     let start: u64 = request
+//!         .last_event_id()
+//!         .and_then(|id| id.parse().ok())
+//!         .map_or(0, |id: u64| id + 1);
+//!
+//!     Response::sse(stream::iter(
+//!         (start..start + 3).map(|id| Event::data("tick").id(id.to_string())),
+//!     ))
+//! }
+//! ```
+
+use std::{fmt, io, time::Duration};
+
+use futures::{stream, Stream, StreamExt};
+
+use super::{header::Header, Response};
+
+/// Interval of the keep-alive comments of `Response::sse`
+pub const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Event of a text/event-stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: String,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Event with data, a multiline data is sent in several `data` fields
+    pub fn data<T: Into<String>>(data: T) -> Self {
+        Self {
+            data: data.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Id sent back by the client in Last-Event-ID when it reconnects
+    pub fn id<T: Into<String>>(mut self, id: T) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Type of event, `message` for the client when there is none
+    pub fn event<T: Into<String>>(mut self, event: T) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Time the client waits before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // A line break in id or event would start another field
+        let single_line = |value: &str| value.replace(['\r', '\n'], "");
+
+        if let Some(id) = &self.id {
+            writeln!(f, "id: {}", single_line(id))?;
+        }
+
+        if let Some(event) = &self.event {
+            writeln!(f, "event: {}", single_line(event))?;
+        }
+
+        if let Some(retry) = self.retry {
+            writeln!(f, "retry: {}", retry.as_millis())?;
+        }
+
+        for line in self.data.split('\n') {
+            writeln!(f, "data: {}", line.strip_suffix('\r').unwrap_or(line))?;
+        }
+
+        writeln!(f)
+    }
+}
+
+impl Response {
+    /// Server-Sent Events of a stream, with a keep-alive comment every 15 seconds
+    ///
+    /// The connection stays open until the stream ends
+    ///
+    /// # Arguments
+    ///
+    /// * events - Stream of Event
+    pub fn sse<S>(events: S) -> Response
+    where
+        S: Stream<Item = Event> + Send + 'static,
+    {
+        Self::sse_with_keep_alive(events, Some(KEEP_ALIVE))
+    }
+
+    /// Server-Sent Events of a stream
+    ///
+    /// # Arguments
+    ///
+    /// * events - Stream of Event
+    /// * keep_alive - Interval of the comments sent without events, `None` to send none
+    pub fn sse_with_keep_alive<S>(events: S, keep_alive: Option<Duration>) -> Response
+    where
+        S: Stream<Item = Event> + Send + 'static,
+    {
+        let chunks = stream::unfold(events.boxed(), move |mut events| async move {
+            let chunk = match keep_alive {
+                Some(interval) => match tokio::time::timeout(interval, events.next()).await {
+                    Ok(event) => event?.to_string(),
+                    Err(_) => ":\n\n".to_string(),
+                },
+                None => events.next().await?.to_string(),
+            };
+
+            Some((Ok::<_, io::Error>(chunk.into_bytes()), events))
+        });
+
+        let mut response = Response::stream(chunks);
+
+        response.add_multiple_headers(vec![
+            (Header::ContentType, "text/event-stream".to_string()),
+            (Header::CacheControl, "no-cache".to_string()),
+        ]);
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Body;
+
+    #[test]
+    fn it_formats_the_fields() {
+        let event = Event::data("hello\r\nworld")
+            .id("7\n")
+            .event("greeting")
+            .retry(Duration::from_secs(3));
+
+        assert_eq!(
+            event.to_string(),
+            "id: 7\nevent: greeting\nretry: 3000\ndata: hello\ndata: world\n\n"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_sends_keep_alive_comments() {
+        let events = stream::once(async {
+            tokio::time::sleep(Duration::from_secs(20)).await;
+            Event::data("late")
+        });
+
+        let response = Response::sse_with_keep_alive(events, Some(Duration::from_secs(15)));
+        let Body::STREAM(body) = response.get_body() else {
+            panic!("SSE body is not a stream");
+        };
+
+        let chunks: Vec<_> = body.take().unwrap().map(Result::unwrap).collect().await;
+
+        assert_eq!(chunks, vec![b":\n\n".to_vec(), b"data: late\n\n".to_vec()]);
+    }
+}
@@ -0,0 +1,699 @@
+//! Directory mounted on a path, its files are looked for on each request
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+pub use pillow_fs::embed::{EmbeddedDir, EmbeddedFile};
+
+use super::{
+    compression::{add_vary, Encoding},
+    header::Header,
+    static_files::{conditional_response, content_etag, Precompressed, StaticFile},
+    status_code::{Redirection, StatusCode},
+    Body, Response,
+};
+use crate::{http_methods::HttpMethods, Request, Uri};
+
+/// Files sent from the disk whose ETag is kept by a StaticDir
+const MAX_FILES: usize = 4096;
+
+/// Directory served under a prefix of the paths
+///
+/// The files added after the start are served, and the paths that leave
+/// the directory with `..` or a symbolic link are not found
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_http::static_dir::StaticDir;
+///
+/// let mut dir = StaticDir::new("/static", "public");
+///
+/// dir.listing(true)
+///     .cache(64 * 1024 * 1024)
+///     .cache_control("assets", "public, max-age=31536000, immutable");
+///
+/// static PUBLIC: pillow_fs::EmbeddedDir = pillow_fs::embed_dir!("src");
+///
+/// let embedded = StaticDir::embedded("/static", PUBLIC);
+/// ```
+#[derive(Debug)]
+pub struct StaticDir {
+    /// Path of the mount, without the last slash
+    prefix: String,
+
+    root: PathBuf,
+
+    /// Page sent for a directory
+    index: Option<String>,
+
+    listing: bool,
+
+    /// Cache-Control of the files of each subdirectory
+    cache_control: Vec<(PathBuf, String)>,
+
+    cache: Option<Mutex<Cache>>,
+
+    /// Files sent from the disk, with the ETag of their last version
+    files: Mutex<HashMap<PathBuf, StaticFile>>,
+
+    /// Files baked into the binary, sent instead of the disk
+    embedded: Option<EmbeddedDir>,
+}
+
+impl StaticDir {
+    /// Directory with index.html pages, without listing nor cache
+    ///
+    /// # Arguments
+    ///
+    /// * prefix - Path of the mount, like `/static`
+    /// * directory - Directory of the files, it can be created later
+    pub fn new(prefix: &str, directory: &str) -> Self {
+        Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            root: PathBuf::from(directory),
+            index: Some("index.html".to_string()),
+            listing: false,
+            cache_control: Vec::new(),
+            cache: None,
+            files: Mutex::default(),
+            embedded: None,
+        }
+    }
+
+    /// Directory baked into the binary with `embed_dir!`, the cache is not used
+    pub fn embedded(prefix: &str, directory: EmbeddedDir) -> Self {
+        Self {
+            embedded: Some(directory),
+            ..Self::new(prefix, directory.path())
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// File sent for a directory, `None` to send none
+    pub fn index(&mut self, index: Option<&str>) -> &mut Self {
+        self.index = index.map(str::to_string);
+        self
+    }
+
+    /// Pages with the files of the directories without index
+    pub fn listing(&mut self, listing: bool) -> &mut Self {
+        self.listing = listing;
+        self
+    }
+
+    /// Keep the files in memory, the least recently used are removed first
+    ///
+    /// # Arguments
+    ///
+    /// * capacity - Bytes of the files in memory, a bigger file is read from the disk
+    pub fn cache(&mut self, capacity: u64) -> &mut Self {
+        self.cache = Some(Mutex::new(Cache::new(capacity)));
+        self
+    }
+
+    /// Cache-Control of the files of a subdirectory, `""` for the whole directory
+    ///
+    /// The most specific subdirectory wins
+    pub fn cache_control(&mut self, directory: &str, cache_control: &str) -> &mut Self {
+        let directory = self.root.join(directory.trim_matches('/'));
+
+        self.cache_control.retain(|(dir, _)| *dir != directory);
+        self.cache_control
+            .push((directory, cache_control.to_string()));
+        self
+    }
+
+    /// Response to a GET or HEAD request of the directory
+    ///
+    /// `None` when the path is not under the prefix or not found
+    pub fn response(&self, request: &Request) -> Option<Response> {
+        if !matches!(request.method(), HttpMethods::GET | HttpMethods::HEAD) {
+            return None;
+        }
+
+        let uri = request.uri().0.as_str();
+        let rest = uri.strip_prefix(self.prefix.as_str())?;
+
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+
+        if let Some(directory) = &self.embedded {
+            return self.embedded_response(directory, rest, request);
+        }
+
+        let path = self.resolve(&Uri(rest.to_string()))?;
+        let metadata = fs::metadata(&path).ok()?;
+
+        if !metadata.is_dir() {
+            return self.file(path, &metadata, request).ok();
+        }
+
+        if !uri.ends_with('/') {
+            return Some(redirect_to_directory(request));
+        }
+
+        if let Some(index) = &self.index {
+            let index = path.join(index);
+
+            if let Some(metadata) = fs::metadata(&index)
+                .ok()
+                .filter(|metadata| metadata.is_file())
+            {
+                return self.file(index, &metadata, request).ok();
+            }
+        }
+
+        if self.listing {
+            return self
+                .entries(&path)
+                .ok()
+                .map(|entries| listing_page(entries, path == self.root, uri));
+        }
+
+        None
+    }
+
+    /// Path of the file of a path under the prefix, `None` if it's outside of the directory
+    fn resolve(&self, path: &Uri) -> Option<PathBuf> {
+        let mut file = self.root.clone();
+
+        for segment in segments(path)? {
+            file.push(segment);
+        }
+
+        // A symbolic link can point outside
+        let real = file.canonicalize().ok()?;
+        let root = self.root.canonicalize().ok()?;
+
+        real.starts_with(root).then_some(file)
+    }
+
+    fn entries(&self, directory: &Path) -> io::Result<Vec<(String, bool)>> {
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            entries.push((
+                entry.file_name().to_string_lossy().to_string(),
+                entry.file_type()?.is_dir(),
+            ));
+        }
+
+        Ok(entries)
+    }
+
+    fn cache_control_of(&self, file: &Path) -> Option<&str> {
+        self.cache_control
+            .iter()
+            .filter(|(directory, _)| file.starts_with(directory))
+            .max_by_key(|(directory, _)| directory.components().count())
+            .map(|(_, cache_control)| cache_control.as_str())
+    }
+
+    fn file(
+        &self,
+        path: PathBuf,
+        metadata: &fs::Metadata,
+        request: &Request,
+    ) -> io::Result<Response> {
+        let cache_control = self.cache_control_of(&path);
+
+        // The precompressed files are sent from the disk
+        if let Some(cache) = self.cache.as_ref().filter(|cache| {
+            metadata.len() <= lock(cache).capacity && !Precompressed::of(&path, request).vary
+        }) {
+            let cached = lock(cache).get(&path, metadata);
+
+            let (content, etag) = match cached {
+                Some(cached) => cached,
+                None => {
+                    let content: Arc<[u8]> = fs::read(&path)?.into();
+                    let etag = content_etag(&content);
+
+                    lock(cache).insert(path.clone(), content.clone(), etag.clone(), metadata);
+                    (content, etag)
+                }
+            };
+
+            return conditional_response(
+                request,
+                etag,
+                metadata.modified().ok(),
+                cache_control,
+                |etag, last_modified| {
+                    let mut response = Response::new_empty();
+
+                    response.add_multiple_headers(vec![
+                        (
+                            Header::ContentType,
+                            pillow_fs::FS::get_mime_type(&path.to_string_lossy()),
+                        ),
+                        (Header::ETag, etag.to_string()),
+                    ]);
+
+                    if let Some(last_modified) = last_modified {
+                        response.add_header(Header::LastModified, last_modified.to_string());
+                    }
+
+                    response.content = Body::SHARED(content);
+
+                    Ok(response.range(request))
+                },
+            );
+        }
+
+        let mut files = lock(&self.files);
+
+        // Forgetting a file only costs a new hash of its content
+        if files.len() >= MAX_FILES && !files.contains_key(&path) {
+            if let Some(forgotten) = files.keys().next().cloned() {
+                files.remove(&forgotten);
+            }
+        }
+
+        let mut file = files
+            .entry(path.clone())
+            .or_insert_with(|| {
+                StaticFile::from_metadata(path.to_string_lossy().to_string(), metadata.clone())
+            })
+            .clone();
+        drop(files);
+
+        if let Some(cache_control) = cache_control {
+            file.set_cache_control(cache_control);
+        }
+
+        file.response_to(request)
+    }
+
+    fn embedded_response(
+        &self,
+        directory: &EmbeddedDir,
+        rest: &str,
+        request: &Request,
+    ) -> Option<Response> {
+        let path = segments(&Uri(rest.to_string()))?.join("/");
+
+        if let Some(file) = directory.get(&path) {
+            return self.embedded_file(directory, file, request).ok();
+        }
+
+        if !directory.is_dir(&path) {
+            return None;
+        }
+
+        if !request.uri().0.ends_with('/') {
+            return Some(redirect_to_directory(request));
+        }
+
+        if let Some(index) = &self.index {
+            let index = match path.as_str() {
+                "" => index.clone(),
+                path => format!("{path}/{index}"),
+            };
+
+            if let Some(file) = directory.get(&index) {
+                return self.embedded_file(directory, file, request).ok();
+            }
+        }
+
+        if self.listing {
+            let entries = directory
+                .entries(&path)
+                .into_iter()
+                .map(|(name, is_dir)| (name.to_string(), is_dir))
+                .collect();
+
+            return Some(listing_page(entries, path.is_empty(), &request.uri().0));
+        }
+
+        None
+    }
+
+    /// Response with the precompressed sibling of the file preferred by the request
+    fn embedded_file(
+        &self,
+        directory: &EmbeddedDir,
+        file: &EmbeddedFile,
+        request: &Request,
+    ) -> io::Result<Response> {
+        let siblings: Vec<(Encoding, &EmbeddedFile)> =
+            [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip]
+                .into_iter()
+                .filter_map(|encoding| {
+                    let sibling = format!("{}.{}", file.path(), encoding.extension()?);
+                    Some((encoding, directory.get(&sibling)?))
+                })
+                .collect();
+
+        let encodings: Vec<_> = siblings.iter().map(|(encoding, _)| *encoding).collect();
+        let preferred = Encoding::negotiate(request, &encodings);
+
+        let (sent, etag) = match siblings
+            .iter()
+            .find(|(encoding, _)| Some(*encoding) == preferred)
+        {
+            Some((encoding, sibling)) => (
+                *sibling,
+                format!(
+                    "{}-{}\"",
+                    file.etag().trim_end_matches('"'),
+                    encoding.as_str()
+                ),
+            ),
+            None => (file, file.etag().to_string()),
+        };
+
+        let mut response = conditional_response(
+            request,
+            etag,
+            None,
+            self.cache_control_of(&self.root.join(file.path())),
+            |etag, _| {
+                let mut response = Response::new_empty();
+
+                response.add_multiple_headers(vec![
+                    (Header::ContentType, file.content_type().to_string()),
+                    (Header::ETag, etag.to_string()),
+                ]);
+
+                if let Some(encoding) = preferred {
+                    response.add_header(Header::ContentEncoding, encoding.as_str().to_string());
+                }
+
+                response.content = Body::BYTES(sent.content().to_vec());
+
+                Ok(response.range(request))
+            },
+        )?;
+
+        if !siblings.is_empty() {
+            add_vary(&mut response, "Accept-Encoding");
+        }
+
+        Ok(response)
+    }
+}
+
+/// Segments of a path under the prefix, `None` if one of them leaves the directory
+fn segments(path: &Uri) -> Option<Vec<String>> {
+    let mut segments = Vec::new();
+
+    for segment in path.decoded()?.split('/') {
+        match segment {
+            "" | "." => {}
+            segment
+                if segment.contains('\\')
+                    || !matches!(
+                        Path::new(segment).components().collect::<Vec<_>>()[..],
+                        [Component::Normal(_)]
+                    ) =>
+            {
+                return None
+            }
+            segment => segments.push(segment.to_string()),
+        }
+    }
+
+    Some(segments)
+}
+
+/// Redirection to the path with the last slash, the relative links of a page
+/// are resolved from the directory
+fn redirect_to_directory(request: &Request) -> Response {
+    let uri = request.uri().0.as_str();
+
+    let location = match request.query() {
+        Some(query) => format!("{uri}/?{query}"),
+        None => format!("{uri}/"),
+    };
+
+    let mut response = Response::new_empty();
+    response.set_status_code(StatusCode::Redirection(Redirection::MovedPermantely));
+    response.add_header(Header::Location, location);
+
+    response
+}
+
+/// Page with the links to the entries of a directory, without the hidden ones
+fn listing_page(mut entries: Vec<(String, bool)>, is_root: bool, uri: &str) -> Response {
+    entries.retain(|(name, _)| !name.starts_with('.'));
+    entries.sort();
+
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    let title = escape(&Uri(uri.to_string()).decoded().unwrap_or_default());
+    let mut links = String::new();
+
+    if !is_root {
+        links.push_str("<li><a href=\"../\">../</a></li>");
+    }
+
+    for (name, is_dir) in entries {
+        let slash = if is_dir { "/" } else { "" };
+
+        links.push_str(&format!(
+            "<li><a href=\"{}{slash}\">{}{slash}</a></li>",
+            Uri::encode(&name),
+            escape(&name)
+        ));
+    }
+
+    let mut response = Response::new_empty();
+    response.add_header(Header::ContentType, "text/html; charset=utf-8".to_string());
+    response.insert_string_content(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Index of {title}</title></head>\
+             <body><h1>Index of {title}</h1><ul>{links}</ul></body></html>"
+    ));
+
+    response
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Files of a StaticDir in memory, the least recently used are removed first
+#[derive(Debug)]
+struct Cache {
+    /// Bytes of the files in memory
+    capacity: u64,
+
+    size: u64,
+
+    /// Counter of the uses of the files
+    clock: u64,
+
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+#[derive(Debug)]
+struct CachedFile {
+    content: Arc<[u8]>,
+    etag: String,
+    modified: Option<SystemTime>,
+    last_used: u64,
+}
+
+impl Cache {
+    fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            clock: 0,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Content and ETag of a file, `None` if it isn't in memory or it changed
+    fn get(&mut self, path: &Path, metadata: &fs::Metadata) -> Option<(Arc<[u8]>, String)> {
+        self.clock += 1;
+
+        let file = self.files.get(path)?;
+
+        if file.modified != metadata.modified().ok() || file.content.len() as u64 != metadata.len()
+        {
+            self.remove(path);
+            return None;
+        }
+
+        let file = self.files.get_mut(path)?;
+        file.last_used = self.clock;
+
+        Some((file.content.clone(), file.etag.clone()))
+    }
+
+    fn insert(&mut self, path: PathBuf, content: Arc<[u8]>, etag: String, metadata: &fs::Metadata) {
+        let len = content.len() as u64;
+
+        self.remove(&path);
+
+        if len > self.capacity {
+            return;
+        }
+
+        while self.size + len > self.capacity {
+            let Some(least_used) = self
+                .files
+                .iter()
+                .min_by_key(|(_, file)| file.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+
+            self.remove(&least_used);
+        }
+
+        self.clock += 1;
+        self.size += len;
+        self.files.insert(
+            path,
+            CachedFile {
+                content,
+                etag,
+                modified: metadata.modified().ok(),
+                last_used: self.clock,
+            },
+        );
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(file) = self.files.remove(path) {
+            self.size -= file.content.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(dir: &StaticDir, uri: &str) -> Option<Response> {
+        let request = format!("GET {uri} HTTP/1.1\r\n\r\n");
+
+        dir.response(&Request::from_vec(&request.into_bytes()).unwrap())
+    }
+
+    #[test]
+    fn it_serves_the_files_of_the_directory() {
+        let root = std::env::temp_dir().join(format!("pillow-static-dir-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(root.join("docs/a b.txt"), "notes").unwrap();
+
+        let mut dir = StaticDir::new("/static", &root.to_string_lossy());
+        dir.listing(true).cache(1024);
+
+        let home = get(&dir, "/static/").unwrap();
+        assert_eq!(home.get_body().as_bytes(), b"<h1>home</h1>");
+
+        let notes = get(&dir, "/static/docs/a%20b.txt").unwrap();
+        assert_eq!(notes.get_body().as_bytes(), b"notes");
+        assert!(notes.get_header(&Header::ETag).is_some());
+
+        // A cached file is shared with the responses
+        let again = get(&dir, "/static/docs/a%20b.txt").unwrap();
+        match (notes.get_body(), again.get_body()) {
+            (Body::SHARED(first), Body::SHARED(second)) => assert!(Arc::ptr_eq(&first, &second)),
+            _ => panic!("the cached file is copied"),
+        }
+
+        let redirect = get(&dir, "/static/docs").unwrap();
+        assert_eq!(
+            redirect.get_header(&Header::Location),
+            Some("/static/docs/")
+        );
+
+        let listing = get(&dir, "/static/docs/").unwrap().get_body().to_string();
+        assert!(listing.contains("<a href=\"a%20b.txt\">a b.txt</a>"));
+
+        fs::write(root.join("docs/new.txt"), "new").unwrap();
+        assert!(get(&dir, "/static/docs/new.txt").is_some());
+
+        assert!(get(&dir, "/static/../Cargo.toml").is_none());
+        assert!(get(&dir, "/static/docs/..%2F..%2Fetc%2Fpasswd").is_none());
+        assert!(get(&dir, "/statics/index.html").is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn it_removes_the_least_recently_used_files() {
+        let root = std::env::temp_dir().join(format!("pillow-static-lru-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        for name in ["a", "b", "c"] {
+            fs::write(root.join(name), "1234").unwrap();
+        }
+
+        let mut dir = StaticDir::new("/", &root.to_string_lossy());
+        dir.cache(8);
+
+        get(&dir, "/a").unwrap();
+        get(&dir, "/b").unwrap();
+        get(&dir, "/a").unwrap();
+        get(&dir, "/c").unwrap();
+
+        let cache = lock(dir.cache.as_ref().unwrap());
+        assert!(cache.files.contains_key(&root.join("a")));
+        assert!(!cache.files.contains_key(&root.join("b")));
+        assert_eq!(cache.size, 8);
+        drop(cache);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn it_serves_the_embedded_files() {
+        static SOURCES: EmbeddedDir = pillow_fs::embed_dir!("src");
+
+        let mut dir = StaticDir::embedded("/src", SOURCES);
+        dir.listing(true);
+
+        let lib = get(&dir, "/src/lib.rs").unwrap();
+        assert_eq!(lib.get_body().as_bytes(), include_bytes!("../lib.rs"));
+        assert_eq!(lib.get_header(&Header::ContentType), Some("text/x-rust"));
+
+        let etag = lib.get_header(&Header::ETag).unwrap();
+        let request = format!("GET /src/lib.rs HTTP/1.1\r\nIf-None-Match: {etag}\r\n\r\n");
+        let not_modified = dir
+            .response(&Request::from_vec(&request.into_bytes()).unwrap())
+            .unwrap();
+        assert_eq!(
+            not_modified.get_status_code(),
+            &StatusCode::Redirection(Redirection::NotModified)
+        );
+
+        let redirect = get(&dir, "/src/response").unwrap();
+        assert_eq!(
+            redirect.get_header(&Header::Location),
+            Some("/src/response/")
+        );
+
+        let listing = get(&dir, "/src/response/").unwrap().get_body().to_string();
+        assert!(listing.contains("<a href=\"static_dir.rs\">static_dir.rs</a>"));
+
+        assert!(get(&dir, "/src/../Cargo.toml").is_none());
+        assert!(get(&dir, "/src/missing.rs").is_none());
+    }
+}
@@ -1,4 +1,29 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use pillow_fs::FS;
+use sha1::{Digest, Sha1};
+
+use super::compression::{add_vary, Encoding};
+use crate::{
+    header::Header,
+    status_code::{Redirection, StatusCode},
+    Request, Response,
+};
+
+/// ETag of a version of a file, known by its modification date and length
+#[derive(Debug)]
+struct Version {
+    modified: Option<SystemTime>,
+    len: u64,
+    etag: String,
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -6,15 +31,20 @@ pub struct StaticFiles {
     pub files: Vec<StaticFile>,
 }
 
+/// File of a static directory, read from the disk when it's sent
 #[derive(Debug, Clone)]
 pub struct StaticFile {
     // pub kind: StaticFileType,
     pub route_absolute: String,
     pub path: String,
     pub name_file: String,
-    pub content: Vec<u8>,
     pub lenght: usize,
-    file_original: pillow_fs::File,
+    metadata: fs::Metadata,
+    content_type: String,
+    cache_control: Option<String>,
+
+    /// Hash of the last version sent, shared by the clones
+    version: Arc<Mutex<Option<Version>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,18 +60,33 @@ impl Default for StaticFiles {
 }
 
 impl StaticFiles {
+    /// Files of a directory and its subdirectories, without their content
     #[allow(dead_code)]
     pub fn new(directory: &str) -> StaticFiles {
-        let directory_files = FS::get_all_in_directories(directory);
+        let mut files: Vec<StaticFile> = Vec::new();
 
-        let vec_files = Self::normalize(directory_files);
+        let err_msg = format!("No such file or directory ' {} '", directory);
+        Self::walk(Path::new(directory), &mut files).expect(&err_msg);
 
-        let mut files: Vec<StaticFile> = Vec::new();
-        for f in vec_files {
-            files.push(StaticFile::new(f.clone()));
+        StaticFiles { files }
+    }
+
+    fn walk(directory: &Path, files: &mut Vec<StaticFile>) -> io::Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+
+            if metadata.is_dir() {
+                Self::walk(&entry.path(), files)?;
+            } else if metadata.is_file() {
+                files.push(StaticFile::from_metadata(
+                    entry.path().to_string_lossy().to_string(),
+                    metadata,
+                ));
+            }
         }
 
-        StaticFiles { files }
+        Ok(())
     }
 
     pub fn normalize(directory: Vec<pillow_fs::File>) -> Vec<pillow_fs::File> {
@@ -99,37 +144,309 @@ impl StaticFiles {
 
 impl StaticFile {
     pub fn new(file: pillow_fs::File) -> StaticFile {
-        // let re = Regex::new(r"[a-zA-Z]+\.[a-zA-Z]+");
-        // let regex = re.unwrap();
-
-        let route_absolute = format!("{}", &file.path);
-
-        let content = file.clone().content.unwrap();
+        Self::from_metadata(file.path, file.metadata)
+    }
 
+    pub(super) fn from_metadata(route_absolute: String, metadata: fs::Metadata) -> StaticFile {
         let path = format!("/{}", &route_absolute);
 
         StaticFile {
             // kind,
+            content_type: FS::get_mime_type(&route_absolute),
             route_absolute: route_absolute.to_string(),
             path,
-            name_file: route_absolute.to_string(),
-            content: content.clone(),
-            lenght: content.len(),
-            file_original: file.clone(),
+            name_file: route_absolute,
+            lenght: metadata.len() as usize,
+            metadata,
+            cache_control: None,
+            version: Arc::default(),
         }
     }
 }
 
 impl StaticFile {
     pub fn metadata(&self) -> std::fs::Metadata {
-        self.file_original.metadata.clone()
+        self.metadata.clone()
     }
 
     pub fn file_type(&self) -> std::fs::FileType {
-        self.file_original.file_type.clone()
+        self.metadata.file_type()
     }
 
     pub fn content_type(&self) -> Option<String> {
-        self.file_original.clone().content_type
+        Some(self.content_type.clone())
+    }
+
+    /// Cache-Control of the responses, like `public, max-age=31536000, immutable`
+    pub fn set_cache_control(&mut self, cache_control: &str) {
+        self.cache_control = Some(cache_control.to_string());
+    }
+
+    pub fn cache_control(&self) -> Option<&str> {
+        self.cache_control.as_deref()
+    }
+
+    /// Response streaming the file from the disk
+    pub fn response(&self) -> io::Result<Response> {
+        Response::send_file(&self.route_absolute)
+    }
+
+    /// Response to a request, 304 when the client has the current version
+    /// and 206 with the parts asked by a Range header
+    ///
+    /// The responses have a strong ETag, the Last-Modified of the file and
+    /// the Cache-Control of the file
+    pub fn response_to(&self, request: &Request) -> io::Result<Response> {
+        let metadata = fs::metadata(&self.route_absolute)?;
+        let mut etag = self.etag_of(&metadata)?;
+        let precompressed = Precompressed::of(Path::new(&self.route_absolute), request);
+
+        let path = match &precompressed.file {
+            Some((encoding, path)) => {
+                // Each encoding is another representation with its own ETag
+                etag = format!("{}-{}\"", etag.trim_end_matches('"'), encoding.as_str());
+                path.clone()
+            }
+            None => PathBuf::from(&self.route_absolute),
+        };
+
+        let mut response = conditional_response(
+            request,
+            etag,
+            metadata.modified().ok(),
+            self.cache_control.as_deref(),
+            |etag, last_modified| {
+                let mut response = Response::send_file_range(
+                    &path,
+                    &self.content_type,
+                    request,
+                    Some(etag),
+                    last_modified,
+                )?;
+
+                if let Some((encoding, _)) = &precompressed.file {
+                    response.add_header(Header::ContentEncoding, encoding.as_str().to_string());
+                }
+
+                Ok(response)
+            },
+        )?;
+
+        if precompressed.vary {
+            add_vary(&mut response, "Accept-Encoding");
+        }
+
+        Ok(response)
+    }
+
+    /// Strong ETag of the current content of the file
+    ///
+    /// The content is hashed again only when the file changes
+    pub fn etag(&self) -> io::Result<String> {
+        self.etag_of(&fs::metadata(&self.route_absolute)?)
+    }
+
+    fn etag_of(&self, metadata: &fs::Metadata) -> io::Result<String> {
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+
+        let mut version = self.version.lock().unwrap_or_else(|err| err.into_inner());
+
+        match version.as_ref() {
+            Some(version) if version.modified == modified && version.len == len => {
+                Ok(version.etag.clone())
+            }
+            _ => {
+                let etag = Self::hash(&self.route_absolute)?;
+
+                *version = Some(Version {
+                    modified,
+                    len,
+                    etag: etag.clone(),
+                });
+
+                Ok(etag)
+            }
+        }
+    }
+
+    fn hash(path: &str) -> io::Result<String> {
+        let mut file = fs::File::open(path)?;
+        let mut sha1 = Sha1::new();
+        let mut chunk = vec![0; 64 * 1024];
+
+        loop {
+            match file.read(&mut chunk)? {
+                0 => return Ok(etag(sha1)),
+                read => sha1.update(&chunk[..read]),
+            }
+        }
+    }
+}
+
+/// Precompressed siblings of a file, like `app.js.br` for `app.js`
+pub(super) struct Precompressed {
+    /// The file has siblings, its responses vary with Accept-Encoding
+    pub(super) vary: bool,
+
+    /// Sibling of the encoding preferred by the request
+    pub(super) file: Option<(Encoding, PathBuf)>,
+}
+
+impl Precompressed {
+    pub(super) fn of(path: &Path, request: &Request) -> Self {
+        let mut siblings = Vec::new();
+
+        for encoding in [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip] {
+            let Some(extension) = encoding.extension() else {
+                continue;
+            };
+
+            let mut sibling = path.as_os_str().to_owned();
+            sibling.push(format!(".{extension}"));
+            let sibling = PathBuf::from(sibling);
+
+            if sibling.is_file() {
+                siblings.push((encoding, sibling));
+            }
+        }
+
+        let encodings: Vec<_> = siblings.iter().map(|(encoding, _)| *encoding).collect();
+        let preferred = Encoding::negotiate(request, &encodings);
+
+        Self {
+            vary: !siblings.is_empty(),
+            file: siblings
+                .into_iter()
+                .find(|(encoding, _)| Some(*encoding) == preferred),
+        }
+    }
+}
+
+/// Strong ETag of a content
+pub(super) fn content_etag(content: &[u8]) -> String {
+    etag(Sha1::new_with_prefix(content))
+}
+
+fn etag(sha1: Sha1) -> String {
+    format!("\"{}\"", URL_SAFE_NO_PAD.encode(sha1.finalize()))
+}
+
+/// 304 when the client has the current version of a content, else the response of `send`
+///
+/// The responses have the ETag, the Last-Modified and the Cache-Control of the content
+///
+/// # Arguments
+///
+/// * send - Called with the ETag and the Last-Modified
+pub(super) fn conditional_response<F>(
+    request: &Request,
+    etag: String,
+    modified: Option<SystemTime>,
+    cache_control: Option<&str>,
+    send: F,
+) -> io::Result<Response>
+where
+    F: FnOnce(&str, Option<&str>) -> io::Result<Response>,
+{
+    let last_modified = modified.map(crate::date::http_date);
+
+    let mut response = if request.is_fresh(&etag, modified) {
+        let mut response = Response::new_empty();
+        response.set_status_code(StatusCode::Redirection(Redirection::NotModified));
+        response
+    } else {
+        send(&etag, last_modified.as_deref())?
+    };
+
+    response.add_header(Header::ETag, etag);
+
+    if let Some(last_modified) = last_modified {
+        response.add_header(Header::LastModified, last_modified);
+    }
+
+    if let Some(cache_control) = cache_control {
+        response.add_header(Header::CacheControl, cache_control.to_string());
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_answers_conditional_requests() {
+        let path = std::env::temp_dir().join(format!("pillow-etag-{}.css", std::process::id()));
+        std::fs::write(&path, "body {}").unwrap();
+
+        let mut file = StaticFile::from_metadata(
+            path.to_string_lossy().to_string(),
+            std::fs::metadata(&path).unwrap(),
+        );
+        file.set_cache_control("no-cache");
+
+        let response = file.response_to(&Request::new_empty()).unwrap();
+        let etag = file.etag().unwrap();
+        let headers = response.get_headers();
+
+        assert!(headers.contains(&format!("ETag: {}", etag)));
+        assert!(headers.contains("Last-Modified: "));
+        assert!(headers.contains("Cache-Control: no-cache"));
+
+        let request = format!("GET / HTTP/1.1\r\nIf-None-Match: W/{}\r\n\r\n", etag);
+        let request = Request::from_vec(&request.into_bytes()).unwrap();
+        let response = file.response_to(&request).unwrap();
+
+        assert_eq!(
+            response.get_status_code(),
+            &StatusCode::Redirection(Redirection::NotModified)
+        );
+
+        std::fs::write(&path, "body { margin: 0 }").unwrap();
+        let response = file.response_to(&request).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_ne!(file.etag().ok(), Some(etag));
+        assert!(matches!(
+            response.get_body(),
+            crate::BodyResponse::STREAM(_)
+        ));
+    }
+
+    #[test]
+    fn it_sends_the_precompressed_siblings() {
+        let path =
+            std::env::temp_dir().join(format!("pillow-precompressed-{}.js", std::process::id()));
+        let sibling = path.with_extension("js.gz");
+        std::fs::write(&path, "let a = 1;").unwrap();
+        std::fs::write(&sibling, "gzipped").unwrap();
+
+        let file = StaticFile::from_metadata(
+            path.to_string_lossy().to_string(),
+            std::fs::metadata(&path).unwrap(),
+        );
+
+        let request = "GET / HTTP/1.1\r\nAccept-Encoding: gzip, br\r\n\r\n";
+        let request = Request::from_vec(&request.as_bytes().to_vec()).unwrap();
+        let gzip = file.response_to(&request).unwrap();
+        let identity = file.response_to(&Request::new_empty()).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&sibling).unwrap();
+
+        assert_eq!(gzip.get_header(&Header::ContentEncoding), Some("gzip"));
+        assert_eq!(
+            gzip.get_header(&Header::ContentType),
+            file.content_type().as_deref()
+        );
+        assert_eq!(gzip.get_header(&Header::Vary), Some("Accept-Encoding"));
+        assert_ne!(
+            gzip.get_header(&Header::ETag),
+            identity.get_header(&Header::ETag)
+        );
+        assert_eq!(identity.get_header(&Header::ContentEncoding), None);
+        assert_eq!(identity.get_header(&Header::Vary), Some("Accept-Encoding"));
     }
 }
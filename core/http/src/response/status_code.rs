@@ -157,6 +157,13 @@ pub enum ClientError {
     /// This response is used much more since some browsers, like Chrome, Firefox 27+, or IE9, use HTTP pre-connection mechanisms to speed up surfing.
     /// Also note that some servers merely shut down the connection without sending this message.
     RequestTimeout,
+    /// Request entity is larger than limits defined by server.
+    /// The server might close the connection or return a Retry-After header field.
+    PayloadTooLarge,
+    /// The ranges of the Range header are outside of the content.
+    RangeNotSatisfiable,
+    /// The server refuses to perform the request using the current protocol but might be willing to do so after the client upgrades to a different protocol.
+    UpgradeRequired,
 }
 
 impl AsStr for ClientError {
@@ -169,6 +176,9 @@ impl AsStr for ClientError {
             ClientError::MethodNotAllowed => "405 Method Not Allowed",
             ClientError::NotAcceptable => "406 Not Acceptable",
             ClientError::RequestTimeout => "408 Request Timeout",
+            ClientError::PayloadTooLarge => "413 Payload Too Large",
+            ClientError::RangeNotSatisfiable => "416 Range Not Satisfiable",
+            ClientError::UpgradeRequired => "426 Upgrade Required",
         }
     }
 }
@@ -183,6 +193,9 @@ pub enum ServerError {
     NotImplemented,
     /// This error response means that the server, while working as a gateway to get a response needed to handle the request, got an invalid response.
     BadGateway,
+    /// The server is not ready to handle the request.
+    /// Common causes are a server that is down for maintenance or that is overloaded.
+    ServiceUnavailable,
     /// This error response is given when the server is acting as a gateway and cannot get a response in time.
     GatewayTimeout,
 }
@@ -193,6 +206,7 @@ impl AsStr for ServerError {
             ServerError::InternalServerError => "500 Internal Server Error",
             ServerError::NotImplemented => "501 Not Implemented",
             ServerError::BadGateway => "502 Bad Gateway",
+            ServerError::ServiceUnavailable => "503 Service Unavailable",
             ServerError::GatewayTimeout => "504 Gateway Timeout",
         }
     }
@@ -0,0 +1,227 @@
+use std::{
+    fmt,
+    io::{self, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use futures::{stream, stream::BoxStream, Stream, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::{header::Header, Body, Response};
+
+type Chunks = BoxStream<'static, io::Result<Vec<u8>>>;
+
+/// Size of the chunks read from a file
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Body sent while it's produced
+///
+/// With a length the body is sent with Content-Length, without
+/// with chunked transfer encoding.
+/// The clones of a Response share the stream, it's sent once
+#[derive(Clone)]
+pub struct BodyStream {
+    chunks: Arc<Mutex<Option<Chunks>>>,
+    len: Option<u64>,
+}
+
+impl BodyStream {
+    /// Body of the chunks of a stream
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        Self {
+            chunks: Arc::new(Mutex::new(Some(stream.boxed()))),
+            len: None,
+        }
+    }
+
+    /// Body of the chunks of a stream of `len` bytes
+    pub fn with_len<S>(stream: S, len: u64) -> Self
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        Self {
+            len: Some(len),
+            ..Self::new(stream)
+        }
+    }
+
+    /// Body of a file, read by chunks of 64 KiB
+    pub fn from_file(file: std::fs::File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+
+        Ok(Self::with_len(file_chunks(file, 0, len), len))
+    }
+
+    /// Length of the body, `None` when it's unknown
+    pub fn content_length(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Take the stream to send it, `None` if it was already sent
+    pub fn take(&self) -> Option<Chunks> {
+        self.chunks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+}
+
+/// Chunks of the `len` bytes of a file from `start`
+///
+/// Each chunk is read at its position, the clones of a file can be read in turn
+pub(super) fn file_chunks(
+    file: std::fs::File,
+    start: u64,
+    len: u64,
+) -> impl Stream<Item = io::Result<Vec<u8>>> + Send + 'static {
+    let file = tokio::fs::File::from_std(file);
+
+    stream::try_unfold(
+        (file, start, len),
+        |(mut file, position, remaining)| async move {
+            if remaining == 0 {
+                return Ok(None);
+            }
+
+            file.seek(SeekFrom::Start(position)).await?;
+
+            let mut chunk = vec![0; remaining.min(FILE_CHUNK_SIZE as u64) as usize];
+            let read = file.read(&mut chunk).await?;
+
+            if read == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The file is shorter than expected",
+                ));
+            }
+
+            chunk.truncate(read);
+            let read = read as u64;
+
+            Ok(Some((chunk, (file, position + read, remaining - read))))
+        },
+    )
+}
+
+impl fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyStream")
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl PartialEq for BodyStream {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.chunks, &other.chunks)
+    }
+}
+
+impl Eq for BodyStream {}
+
+impl Response {
+    /// Send the chunks of a stream, with chunked transfer encoding
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use futures::stream;
+    /// use pillow_http::{header::ContentType, Response};
+    ///
+    /// let mut response = Response::stream(stream::iter(vec![
+    ///     Ok(b"hello ".to_vec()),
+    ///     Ok(b"world".to_vec()),
+    /// ]));
+    /// response.content_type(ContentType::Text);
+    /// ```
+    pub fn stream<S>(stream: S) -> Response
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        Self::with_stream(BodyStream::new(stream))
+    }
+
+    /// Send the chunks of a stream of `len` bytes, with Content-Length
+    ///
+    /// The connection is closed if the stream doesn't have `len` bytes
+    pub fn stream_with_len<S>(stream: S, len: u64) -> Response
+    where
+        S: Stream<Item = io::Result<Vec<u8>>> + Send + 'static,
+    {
+        Self::with_stream(BodyStream::with_len(stream, len))
+    }
+
+    /// Send a file without loading it in memory
+    ///
+    /// The Content-Type is guessed from the extension
+    ///
+    /// # Arguments
+    ///
+    /// * path - Path of the file
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_http::{Request, Response};
+    ///
+    /// fn download(_: &Request) -> Response {
+    ///     Response::send_file("downloads/dataset.tar.gz").unwrap_or_else(|_| Response::text("Not found"))
+    /// }
+    /// ```
+    pub fn send_file<P: AsRef<Path>>(path: P) -> io::Result<Response> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+
+        if !file.metadata()?.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "Not a file"));
+        }
+
+        let mut response = Self::with_stream(BodyStream::from_file(file)?);
+        response.add_header(
+            Header::ContentType,
+            pillow_fs::FS::get_mime_type(&path.to_string_lossy()),
+        );
+
+        Ok(response)
+    }
+
+    pub(super) fn with_stream(body: BodyStream) -> Response {
+        let mut response = Response::new_empty();
+        response.content = Body::STREAM(body);
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_sends_files_by_chunks() {
+        let path =
+            std::env::temp_dir().join(format!("pillow-send-file-{}.txt", std::process::id()));
+        let content = vec![b'a'; FILE_CHUNK_SIZE + 10];
+        std::fs::write(&path, &content).unwrap();
+
+        let response = Response::send_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(response.get_headers().contains("Content-Type: text/plain"));
+
+        let Body::STREAM(body) = response.get_body() else {
+            panic!("file body is not a stream");
+        };
+        assert_eq!(body.content_length(), Some(content.len() as u64));
+
+        let chunks: Vec<_> = body.take().unwrap().map(Result::unwrap).collect().await;
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.concat(), content);
+        assert!(body.take().is_none());
+    }
+}
@@ -15,6 +15,55 @@ impl Uri {
     pub fn value(&self) -> String {
         self.0.clone()
     }
+
+    /// Path with the %XX escapes decoded, `None` if it's not valid UTF-8
+    pub fn decoded(&self) -> Option<String> {
+        let bytes = self.0.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+
+        while i < bytes.len() {
+            let escaped = bytes
+                .get(i + 1..i + 3)
+                .filter(|_| bytes[i] == b'%')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            match escaped {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                }
+                None => {
+                    decoded.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+
+        String::from_utf8(decoded).ok()
+    }
+
+    /// Segment of a path with the reserved characters escaped
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::Uri;
+    ///
+    /// assert_eq!(Uri::encode("a b#1.txt"), "a%20b%231.txt");
+    /// ```
+    pub fn encode(segment: &str) -> String {
+        segment
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (byte as char).to_string()
+                }
+                byte => format!("%{byte:02X}"),
+            })
+            .collect()
+    }
 }
 
 impl std::fmt::Display for Uri {
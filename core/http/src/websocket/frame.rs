@@ -0,0 +1,132 @@
+/// Opcode of a WebSocket frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpCode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl OpCode {
+    fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0x0 => Some(OpCode::Continuation),
+            0x1 => Some(OpCode::Text),
+            0x2 => Some(OpCode::Binary),
+            0x8 => Some(OpCode::Close),
+            0x9 => Some(OpCode::Ping),
+            0xA => Some(OpCode::Pong),
+            _ => None,
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            OpCode::Continuation => 0x0,
+            OpCode::Text => 0x1,
+            OpCode::Binary => 0x2,
+            OpCode::Close => 0x8,
+            OpCode::Ping => 0x9,
+            OpCode::Pong => 0xA,
+        }
+    }
+
+    /// Close, ping and pong
+    pub(crate) fn is_control(&self) -> bool {
+        matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+}
+
+/// First two bytes of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FrameHeader {
+    pub(crate) fin: bool,
+    pub(crate) opcode: OpCode,
+    pub(crate) masked: bool,
+
+    /// Length in the 7 bits, 126 and 127 announce 2 and 8 bytes more
+    pub(crate) len: u8,
+}
+
+impl FrameHeader {
+    /// Parse the first two bytes, `Err` with the reason of the protocol error
+    pub(crate) fn parse(bytes: [u8; 2]) -> Result<Self, &'static str> {
+        if bytes[0] & 0x70 != 0 {
+            return Err("Reserved bits are set without extension");
+        }
+
+        let opcode = OpCode::from_u8(bytes[0] & 0x0F).ok_or("Reserved opcode")?;
+        let fin = bytes[0] & 0x80 != 0;
+        let len = bytes[1] & 0x7F;
+
+        if opcode.is_control() && (!fin || len > 125) {
+            return Err("Control frames can't be fragmented or longer than 125 bytes");
+        }
+
+        Ok(Self {
+            fin,
+            opcode,
+            masked: bytes[1] & 0x80 != 0,
+            len,
+        })
+    }
+}
+
+/// Frame sent by the server, never masked
+pub(crate) fn encode(opcode: OpCode, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+
+    frame.push(0x80 | opcode.as_u8());
+
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+
+    frame
+}
+
+/// Unmask the payload of a client frame
+pub(crate) fn unmask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_the_payload_length() {
+        assert_eq!(encode(OpCode::Text, b"hi"), vec![0x81, 2, b'h', b'i']);
+        assert_eq!(
+            &encode(OpCode::Binary, &[0; 200])[..4],
+            &[0x82, 126, 0, 200]
+        );
+        assert_eq!(
+            &encode(OpCode::Binary, &[0; 70_000])[..10],
+            &[0x82, 127, 0, 0, 0, 0, 0, 1, 0x11, 0x70]
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_headers() {
+        assert!(FrameHeader::parse([0x81, 0x85]).is_ok());
+        assert!(FrameHeader::parse([0xC1, 0x85]).is_err());
+        assert!(FrameHeader::parse([0x83, 0x85]).is_err());
+        assert!(FrameHeader::parse([0x09, 0x80]).is_err());
+        assert!(FrameHeader::parse([0x89, 0xFE]).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use super::{close_code, Message, WebSocket};
+
+/// What the hub does with a client whose queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlowConsumer {
+    /// Drop the message for this client
+    #[default]
+    Drop,
+
+    /// Disconnect the client, its WebSocket is closed with 1008
+    Disconnect,
+}
+
+/// Settings of a Hub
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HubConfig {
+    /// Messages waiting to be sent to a client
+    pub capacity: usize,
+
+    pub slow_consumer: SlowConsumer,
+}
+
+impl Default for HubConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            slow_consumer: SlowConsumer::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+
+    /// Queue of each connected client
+    clients: HashMap<u64, mpsc::Sender<Message>>,
+
+    /// Clients of each channel
+    channels: HashMap<String, HashSet<u64>>,
+}
+
+impl State {
+    /// Remove the client from the hub and the empty channels
+    fn remove(&mut self, id: u64) {
+        self.clients.remove(&id);
+
+        self.channels.retain(|_, clients| {
+            clients.remove(&id);
+            !clients.is_empty()
+        });
+    }
+}
+
+/// Channels of WebSocket clients in the process
+///
+/// The hub is cloned in the handlers that need it, the clones share the channels
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_http::websocket::{Hub, WebSocket};
+///
+/// // router.websocket("/chat", move |ws| chat(hub.clone(), ws));
+/// async fn chat(hub: Hub, ws: WebSocket) {
+///     let client = hub.connect();
+///     client.join("lobby");
+///
+///     let _ = client
+///         .serve(ws, |client, message| {
+///             client.broadcast("lobby", message);
+///         })
+///         .await;
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Hub {
+    state: Arc<Mutex<State>>,
+    config: HubConfig,
+}
+
+impl Hub {
+    /// Hub with the default HubConfig
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: HubConfig) -> Self {
+        Self {
+            state: Arc::default(),
+            config,
+        }
+    }
+
+    /// New client of the hub, removed from the hub when dropped
+    pub fn connect(&self) -> HubClient {
+        let (sender, receiver) = mpsc::channel(self.config.capacity.max(1));
+
+        let mut state = self.state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.clients.insert(id, sender);
+
+        HubClient {
+            id,
+            hub: self.clone(),
+            receiver,
+        }
+    }
+
+    /// Send a message to the clients of a channel
+    ///
+    /// Returns the number of clients that got the message
+    pub fn broadcast(&self, channel: &str, message: Message) -> usize {
+        let mut state = self.state();
+        let mut sent = 0;
+        let mut disconnected = Vec::new();
+
+        let Some(clients) = state.channels.get(channel) else {
+            return 0;
+        };
+
+        for id in clients {
+            let Some(sender) = state.clients.get(id) else {
+                continue;
+            };
+
+            match sender.try_send(message.clone()) {
+                Ok(()) => sent += 1,
+                Err(TrySendError::Full(_)) if self.config.slow_consumer == SlowConsumer::Drop => {}
+                Err(_) => disconnected.push(*id),
+            }
+        }
+
+        for id in disconnected {
+            state.remove(id);
+        }
+
+        sent
+    }
+
+    /// Number of clients in a channel
+    pub fn channel_len(&self, channel: &str) -> usize {
+        self.state().channels.get(channel).map_or(0, HashSet::len)
+    }
+
+    /// Channels with at least a client
+    pub fn channels(&self) -> Vec<String> {
+        self.state().channels.keys().cloned().collect()
+    }
+
+    /// Number of connected clients
+    pub fn client_count(&self) -> usize {
+        self.state().clients.len()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Client of a Hub, usually one per WebSocket
+#[derive(Debug)]
+pub struct HubClient {
+    id: u64,
+    hub: Hub,
+    receiver: mpsc::Receiver<Message>,
+}
+
+impl HubClient {
+    /// Id of the client in the hub
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn hub(&self) -> &Hub {
+        &self.hub
+    }
+
+    /// Join a channel
+    pub fn join(&self, channel: &str) {
+        let mut state = self.hub.state();
+
+        // A disconnected client can't join again
+        if state.clients.contains_key(&self.id) {
+            state
+                .channels
+                .entry(channel.to_string())
+                .or_default()
+                .insert(self.id);
+        }
+    }
+
+    /// Leave a channel
+    pub fn leave(&self, channel: &str) {
+        let mut state = self.hub.state();
+
+        if let Some(clients) = state.channels.get_mut(channel) {
+            clients.remove(&self.id);
+
+            if clients.is_empty() {
+                state.channels.remove(channel);
+            }
+        }
+    }
+
+    /// Send a message to the clients of a channel, see `Hub::broadcast`
+    pub fn broadcast(&self, channel: &str, message: Message) -> usize {
+        self.hub.broadcast(channel, message)
+    }
+
+    /// Next message of the channels, `None` when the hub disconnected the client
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.receiver.recv().await
+    }
+
+    /// Send the messages of the channels to the WebSocket until it closes
+    ///
+    /// # Arguments
+    ///
+    /// * ws - WebSocket of the client
+    /// * on_message - Called with the text and binary messages of the WebSocket
+    pub async fn serve<F>(mut self, mut ws: WebSocket, mut on_message: F) -> io::Result<()>
+    where
+        F: FnMut(&HubClient, Message),
+    {
+        loop {
+            tokio::select! {
+                message = ws.recv() => match message? {
+                    Some(message @ (Message::Text(_) | Message::Binary(_))) => {
+                        on_message(&self, message)
+                    }
+                    Some(_) => {}
+                    None => return Ok(()),
+                },
+
+                message = self.receiver.recv() => match message {
+                    Some(message) => ws.send(message).await?,
+                    None => return ws.close(close_code::POLICY_VIOLATION, "Too slow").await,
+                },
+            }
+        }
+    }
+}
+
+impl Drop for HubClient {
+    fn drop(&mut self) {
+        self.hub.state().remove(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_broadcasts_to_the_channel() {
+        let hub = Hub::new();
+        let mut alice = hub.connect();
+        let mut bob = hub.connect();
+        let carol = hub.connect();
+
+        alice.join("lobby");
+        bob.join("lobby");
+        carol.join("games");
+
+        assert_eq!(hub.broadcast("lobby", Message::text("hi")), 2);
+        assert_eq!(alice.recv().await, Some(Message::text("hi")));
+        assert_eq!(bob.recv().await, Some(Message::text("hi")));
+
+        bob.leave("lobby");
+        assert_eq!(hub.channel_len("lobby"), 1);
+
+        drop(alice);
+        drop(carol);
+        assert!(hub.channels().is_empty());
+        assert_eq!(hub.client_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_handles_slow_consumers() {
+        let hub = Hub::with_config(HubConfig {
+            capacity: 1,
+            slow_consumer: SlowConsumer::Drop,
+        });
+        let mut client = hub.connect();
+        client.join("feed");
+
+        assert_eq!(hub.broadcast("feed", Message::text("1")), 1);
+        assert_eq!(hub.broadcast("feed", Message::text("2")), 0);
+        assert_eq!(client.recv().await, Some(Message::text("1")));
+        assert_eq!(hub.channel_len("feed"), 1);
+
+        let hub = Hub::with_config(HubConfig {
+            capacity: 1,
+            slow_consumer: SlowConsumer::Disconnect,
+        });
+        let mut client = hub.connect();
+        client.join("feed");
+
+        hub.broadcast("feed", Message::text("1"));
+        hub.broadcast("feed", Message::text("2"));
+
+        assert_eq!(hub.channel_len("feed"), 0);
+        assert_eq!(client.recv().await, Some(Message::text("1")));
+        assert_eq!(client.recv().await, None);
+    }
+}
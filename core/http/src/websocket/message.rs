@@ -0,0 +1,70 @@
+/// Message of a WebSocket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+
+    /// Answered with a pong when `auto_pong` is on
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+
+    /// The peer closed the WebSocket
+    Close(Option<CloseFrame>),
+}
+
+impl Message {
+    /// Text message
+    pub fn text<T: Into<String>>(text: T) -> Self {
+        Message::Text(text.into())
+    }
+
+    /// Binary message
+    pub fn binary<B: Into<Vec<u8>>>(bytes: B) -> Self {
+        Message::Binary(bytes.into())
+    }
+
+    /// Text of a text message
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Message::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Payload of the message
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Message::Text(text) => text.as_bytes(),
+            Message::Binary(bytes) | Message::Ping(bytes) | Message::Pong(bytes) => bytes,
+            Message::Close(_) => &[],
+        }
+    }
+}
+
+/// Status code and reason of a close frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+/// Status codes of the close frames
+pub mod close_code {
+    /// The purpose of the connection is fulfilled
+    pub const NORMAL: u16 = 1000;
+
+    /// The server is going down
+    pub const GOING_AWAY: u16 = 1001;
+
+    /// The peer broke the protocol
+    pub const PROTOCOL_ERROR: u16 = 1002;
+
+    /// Text that is not UTF-8
+    pub const INVALID_DATA: u16 = 1007;
+
+    /// The peer broke a policy of the server, like reading too slowly
+    pub const POLICY_VIOLATION: u16 = 1008;
+
+    /// Message bigger than `max_message_size`
+    pub const TOO_BIG: u16 = 1009;
+}
@@ -0,0 +1,629 @@
+//! WebSockets (RFC 6455)
+//!
+//! The server answers the upgrade request of a WebSocket route and gives
+//! the connection to the handler of the route as a [`WebSocket`].
+
+use std::{fmt, io};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{header::Header, http_methods::HttpMethods, Request};
+
+mod frame;
+mod hub;
+mod message;
+
+use frame::{FrameHeader, OpCode};
+pub use hub::{Hub, HubClient, HubConfig, SlowConsumer};
+pub use message::{close_code, CloseFrame, Message};
+
+/// GUID of RFC 6455 to compute Sec-WebSocket-Accept
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Sec-WebSocket-Accept of a Sec-WebSocket-Key
+///
+/// # Examples
+///
+/// ```rust
+/// use pillow_http::websocket::accept_key;
+///
+/// assert_eq!(
+///     accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+///     "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+/// );
+/// ```
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(GUID.as_bytes());
+
+    STANDARD.encode(sha1.finalize())
+}
+
+/// The Sec-WebSocket-Key is the base64 of 16 bytes
+pub(crate) fn is_valid_key(key: &str) -> bool {
+    STANDARD
+        .decode(key.trim())
+        .is_ok_and(|nonce| nonce.len() == 16)
+}
+
+/// The request asks to upgrade the connection to a WebSocket
+pub fn is_upgrade(request: &Request) -> bool {
+    let has_token = |header: &Header, token: &str| {
+        request.headers().get(header).is_some_and(|value| {
+            value
+                .split(',')
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        })
+    };
+
+    request.method() == &HttpMethods::GET
+        && has_token(&Header::Upgrade, "websocket")
+        && has_token(&Header::Connection, "upgrade")
+}
+
+/// First protocol of Sec-WebSocket-Protocol supported by the route
+///
+/// # Arguments
+///
+/// * supported - Subprotocols of the route
+pub fn negotiate_protocol(request: &Request, supported: &[String]) -> Option<String> {
+    request
+        .headers()
+        .get(&Header::SecWebSocketProtocol)?
+        .split(',')
+        .map(str::trim)
+        .find(|protocol| supported.iter().any(|supported| supported == protocol))
+        .map(String::from)
+}
+
+/// Limits and behavior of a WebSocket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebSocketConfig {
+    /// Max size in bytes of a message, fragments included
+    pub max_message_size: usize,
+
+    /// Max size in bytes of a frame
+    pub max_frame_size: usize,
+
+    /// Answer the pings with a pong
+    pub auto_pong: bool,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16 * 1024 * 1024,
+            max_frame_size: 16 * 1024 * 1024,
+            auto_pong: true,
+        }
+    }
+}
+
+/// Connection upgraded to a WebSocket
+trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+/// Why a frame could not be read
+enum ReadError {
+    Io(io::Error),
+
+    /// The WebSocket is closed with the code and the reason
+    Close(u16, &'static str),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+/// WebSocket given to the handler of a route
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pillow_http::websocket::{Message, WebSocket};
+///
+/// async fn echo(mut ws: WebSocket) {
+///     while let Ok(Some(message)) = ws.recv().await {
+///         match message {
+///             Message::Text(_) | Message::Binary(_) => {
+///                 if ws.send(message).await.is_err() {
+///                     break;
+///                 }
+///             }
+///             _ => {}
+///         }
+///     }
+/// }
+/// ```
+pub struct WebSocket {
+    stream: Box<dyn Stream>,
+
+    /// Bytes already read from the stream
+    buffer: Vec<u8>,
+
+    request: Request,
+    protocol: Option<String>,
+    config: WebSocketConfig,
+
+    /// Opcode and data of a fragmented message
+    fragment: Option<(OpCode, Vec<u8>)>,
+
+    /// Frames not written yet, like the pongs of the pings received
+    pending: Vec<u8>,
+
+    close_sent: bool,
+    closed: bool,
+}
+
+impl fmt::Debug for WebSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSocket")
+            .field("request", &self.request)
+            .field("protocol", &self.protocol)
+            .field("config", &self.config)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+impl WebSocket {
+    /// WebSocket over a connection already upgraded
+    ///
+    /// # Arguments
+    ///
+    /// * stream - Connection after the 101 response
+    /// * buffer - Bytes read after the upgrade request
+    /// * request - Upgrade request
+    /// * protocol - Subprotocol sent in the 101 response
+    pub fn new<S>(
+        stream: S,
+        buffer: Vec<u8>,
+        request: Request,
+        protocol: Option<String>,
+        config: WebSocketConfig,
+    ) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        Self {
+            stream: Box::new(stream),
+            buffer,
+            request,
+            protocol,
+            config,
+            fragment: None,
+            pending: Vec::new(),
+            close_sent: false,
+            closed: false,
+        }
+    }
+
+    /// Upgrade request
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
+    /// Subprotocol negotiated with the client
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+
+    /// Next message, `None` when the WebSocket is closed
+    ///
+    /// A protocol error closes the WebSocket and returns an `InvalidData` error.
+    /// The pongs of the pings are sent by the next `recv` or `send`, so it can be
+    /// cancelled in `select!` without losing data
+    pub async fn recv(&mut self) -> io::Result<Option<Message>> {
+        self.flush_pending().await?;
+
+        while !self.closed {
+            match self.read_message().await {
+                Ok(Some(message)) => return Ok(Some(message)),
+                Ok(None) => {}
+                Err(ReadError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.closed = true;
+                }
+                Err(ReadError::Io(err)) => return Err(err),
+                Err(ReadError::Close(code, reason)) => {
+                    let _ = self.send_close(code, reason).await;
+                    let _ = self.stream.shutdown().await;
+                    self.closed = true;
+
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, reason));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Send a message
+    pub async fn send(&mut self, message: Message) -> io::Result<()> {
+        if self.close_sent {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "The WebSocket is closed",
+            ));
+        }
+
+        match message {
+            Message::Text(text) => self.write_frame(OpCode::Text, text.as_bytes()).await,
+            Message::Binary(bytes) => self.write_frame(OpCode::Binary, &bytes).await,
+            Message::Ping(bytes) => self.write_frame(OpCode::Ping, &bytes).await,
+            Message::Pong(bytes) => self.write_frame(OpCode::Pong, &bytes).await,
+            Message::Close(Some(frame)) => self.send_close(frame.code, &frame.reason).await,
+            Message::Close(None) => {
+                self.close_sent = true;
+                self.write_frame(OpCode::Close, &[]).await
+            }
+        }
+    }
+
+    /// Send a text message
+    pub async fn send_text<T: Into<String>>(&mut self, text: T) -> io::Result<()> {
+        self.send(Message::Text(text.into())).await
+    }
+
+    /// Send a binary message
+    pub async fn send_binary<B: Into<Vec<u8>>>(&mut self, bytes: B) -> io::Result<()> {
+        self.send(Message::Binary(bytes.into())).await
+    }
+
+    /// Start the close handshake, `recv` returns `None` after the close of the client
+    pub async fn close(&mut self, code: u16, reason: &str) -> io::Result<()> {
+        if self.close_sent {
+            return Ok(());
+        }
+
+        self.send_close(code, reason).await
+    }
+}
+
+impl WebSocket {
+    /// Read frames until a message is complete
+    async fn read_message(&mut self) -> Result<Option<Message>, ReadError> {
+        let (header, payload) = self.read_frame().await?;
+
+        match header.opcode {
+            OpCode::Text | OpCode::Binary => {
+                if self.fragment.is_some() {
+                    return Err(ReadError::Close(
+                        close_code::PROTOCOL_ERROR,
+                        "Expected a continuation frame",
+                    ));
+                }
+
+                self.check_size(payload.len())?;
+
+                if header.fin {
+                    return Self::data_message(header.opcode, payload).map(Some);
+                }
+
+                self.fragment = Some((header.opcode, payload));
+                Ok(None)
+            }
+
+            OpCode::Continuation => {
+                let size = match &self.fragment {
+                    Some((_, data)) => data.len() + payload.len(),
+                    None => {
+                        return Err(ReadError::Close(
+                            close_code::PROTOCOL_ERROR,
+                            "Continuation frame without a first frame",
+                        ))
+                    }
+                };
+
+                self.check_size(size)?;
+
+                if let Some((_, data)) = &mut self.fragment {
+                    data.extend_from_slice(&payload);
+                }
+
+                match (header.fin, self.fragment.take()) {
+                    (true, Some((opcode, data))) => Self::data_message(opcode, data).map(Some),
+                    (_, fragment) => {
+                        self.fragment = fragment;
+                        Ok(None)
+                    }
+                }
+            }
+
+            OpCode::Ping => {
+                if self.config.auto_pong && !self.close_sent {
+                    self.pending
+                        .extend_from_slice(&frame::encode(OpCode::Pong, &payload));
+                }
+
+                Ok(Some(Message::Ping(payload)))
+            }
+
+            OpCode::Pong => Ok(Some(Message::Pong(payload))),
+
+            OpCode::Close => {
+                let frame = Self::close_frame(&payload)?;
+
+                // Closed before the reply, a cancelled `recv` returns `None` next time
+                self.closed = true;
+
+                if !self.close_sent {
+                    let code = frame
+                        .as_ref()
+                        .map_or(close_code::NORMAL, |frame| frame.code);
+                    self.send_close(code, "").await?;
+                }
+
+                let _ = self.stream.shutdown().await;
+
+                Ok(Some(Message::Close(frame)))
+            }
+        }
+    }
+
+    /// Read a frame and unmask its payload
+    ///
+    /// The bytes stay in the buffer until the frame is complete, so a
+    /// `recv` cancelled by `select!` loses nothing
+    async fn read_frame(&mut self) -> Result<(FrameHeader, Vec<u8>), ReadError> {
+        loop {
+            if let Some(frame) = self.parse_frame()? {
+                return Ok(frame);
+            }
+
+            let mut chunk = [0; 4096];
+
+            match self.stream.read(&mut chunk).await? {
+                0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                len => self.buffer.extend_from_slice(&chunk[..len]),
+            }
+        }
+    }
+
+    /// Frame at the start of the buffer, `None` until it's complete
+    fn parse_frame(&mut self) -> Result<Option<(FrameHeader, Vec<u8>)>, ReadError> {
+        let buffer = &self.buffer;
+
+        if buffer.len() < 2 {
+            return Ok(None);
+        }
+
+        let header = FrameHeader::parse([buffer[0], buffer[1]])
+            .map_err(|reason| ReadError::Close(close_code::PROTOCOL_ERROR, reason))?;
+
+        if !header.masked {
+            return Err(ReadError::Close(
+                close_code::PROTOCOL_ERROR,
+                "Client frames must be masked",
+            ));
+        }
+
+        let (len, offset) = match header.len {
+            126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as u64, 4),
+            127 if buffer.len() >= 10 => {
+                let mut len = [0; 8];
+                len.copy_from_slice(&buffer[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            126 | 127 => return Ok(None),
+            len => (len as u64, 2),
+        };
+
+        if len > self.config.max_frame_size as u64 {
+            return Err(ReadError::Close(close_code::TOO_BIG, "Frame too big"));
+        }
+
+        let end = offset + 4 + len as usize;
+
+        if buffer.len() < end {
+            return Ok(None);
+        }
+
+        let mut mask = [0; 4];
+        mask.copy_from_slice(&buffer[offset..offset + 4]);
+
+        let mut payload = buffer[offset + 4..end].to_vec();
+        frame::unmask(&mut payload, mask);
+        self.buffer.drain(..end);
+
+        Ok(Some((header, payload)))
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), ReadError> {
+        match size > self.config.max_message_size {
+            true => Err(ReadError::Close(close_code::TOO_BIG, "Message too big")),
+            false => Ok(()),
+        }
+    }
+
+    fn data_message(opcode: OpCode, data: Vec<u8>) -> Result<Message, ReadError> {
+        match opcode {
+            OpCode::Text => String::from_utf8(data)
+                .map(Message::Text)
+                .map_err(|_| ReadError::Close(close_code::INVALID_DATA, "Text is not UTF-8")),
+            _ => Ok(Message::Binary(data)),
+        }
+    }
+
+    /// Code and reason of the payload of a close frame
+    fn close_frame(payload: &[u8]) -> Result<Option<CloseFrame>, ReadError> {
+        let invalid = ReadError::Close(close_code::PROTOCOL_ERROR, "Invalid close frame");
+
+        match payload {
+            [] => Ok(None),
+            [_] => Err(invalid),
+            [high, low, reason @ ..] => {
+                let code = u16::from_be_bytes([*high, *low]);
+
+                if !matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999) {
+                    return Err(invalid);
+                }
+
+                let reason = String::from_utf8(reason.to_vec()).map_err(|_| {
+                    ReadError::Close(close_code::INVALID_DATA, "Reason is not UTF-8")
+                })?;
+
+                Ok(Some(CloseFrame { code, reason }))
+            }
+        }
+    }
+
+    async fn send_close(&mut self, code: u16, reason: &str) -> io::Result<()> {
+        self.close_sent = true;
+
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend_from_slice(reason.as_bytes());
+        // The payload of a control frame is at most 125 bytes
+        payload.truncate(125);
+
+        self.write_frame(OpCode::Close, &payload).await
+    }
+
+    /// Write a frame after the pending ones
+    async fn write_frame(&mut self, opcode: OpCode, payload: &[u8]) -> io::Result<()> {
+        self.pending
+            .extend_from_slice(&frame::encode(opcode, payload));
+
+        self.flush_pending().await
+    }
+
+    /// Write the pending frames, the bytes stay pending until they are written
+    async fn flush_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        while !self.pending.is_empty() {
+            let written = self.stream.write(&self.pending).await?;
+
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+
+            self.pending.drain(..written);
+        }
+
+        self.stream.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Frame sent by a client, masked
+    fn client_frame(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![first, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+
+        let mut payload = payload.to_vec();
+        frame::unmask(&mut payload, mask);
+        frame.extend_from_slice(&payload);
+
+        frame
+    }
+
+    fn websocket(config: WebSocketConfig) -> (WebSocket, tokio::io::DuplexStream) {
+        let (client, server) = tokio::io::duplex(4096);
+
+        (
+            WebSocket::new(server, Vec::new(), Request::new_empty(), None, config),
+            client,
+        )
+    }
+
+    #[tokio::test]
+    async fn it_reads_fragmented_messages_and_answers_pings() {
+        let (mut ws, mut client) = websocket(WebSocketConfig::default());
+
+        let mut data = client_frame(0x01, b"hel");
+        data.extend(client_frame(0x89, b"ping"));
+        data.extend(client_frame(0x80, b"lo"));
+        data.extend(client_frame(0x88, &[0x03, 0xE8]));
+        client.write_all(&data).await.unwrap();
+
+        assert_eq!(
+            ws.recv().await.unwrap(),
+            Some(Message::Ping(b"ping".to_vec()))
+        );
+        assert_eq!(ws.recv().await.unwrap(), Some(Message::text("hello")));
+        assert_eq!(
+            ws.recv().await.unwrap(),
+            Some(Message::Close(Some(CloseFrame {
+                code: close_code::NORMAL,
+                reason: String::new(),
+            })))
+        );
+        assert_eq!(ws.recv().await.unwrap(), None);
+
+        let mut sent = Vec::new();
+        client.read_to_end(&mut sent).await.unwrap();
+        assert_eq!(
+            sent,
+            [&[0x8A, 4][..], b"ping", &[0x88, 2, 0x03, 0xE8]].concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_answers_the_pings_of_a_cancelled_recv() {
+        let (mut ws, mut client) = websocket(WebSocketConfig::default());
+
+        client
+            .write_all(&client_frame(0x89, b"ping"))
+            .await
+            .unwrap();
+        assert_eq!(
+            ws.recv().await.unwrap(),
+            Some(Message::Ping(b"ping".to_vec()))
+        );
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), ws.recv()).await;
+        assert!(cancelled.is_err());
+
+        let mut pong = [0; 6];
+        client.read_exact(&mut pong).await.unwrap();
+        assert_eq!(pong, [&[0x8A, 4][..], b"ping"].concat()[..]);
+
+        client.write_all(&client_frame(0x81, b"hi")).await.unwrap();
+        assert_eq!(ws.recv().await.unwrap(), Some(Message::text("hi")));
+    }
+
+    #[tokio::test]
+    async fn it_closes_on_too_big_messages() {
+        let (mut ws, mut client) = websocket(WebSocketConfig {
+            max_message_size: 4,
+            ..WebSocketConfig::default()
+        });
+
+        let mut data = client_frame(0x02, b"abc");
+        data.extend(client_frame(0x80, b"def"));
+        client.write_all(&data).await.unwrap();
+
+        let err = ws.recv().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut close = Vec::new();
+        client.read_to_end(&mut close).await.unwrap();
+        assert_eq!(
+            close,
+            [&[0x88, 17, 0x03, 0xF1][..], b"Message too big"].concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_unmasked_frames() {
+        let (mut ws, mut client) = websocket(WebSocketConfig::default());
+
+        client.write_all(&[0x81, 0x02, b'h', b'i']).await.unwrap();
+
+        assert!(ws.recv().await.is_err());
+        assert!(ws.send_text("late").await.is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use sha1::{Digest, Sha1};
+use syn::LitStr;
+
+/// Arguments of `embed_dir!`, the path of the crate with the types and the directory
+///
+/// The crate is given by the `embed_dir!` of pillow_fs as `$crate`
+pub(crate) fn parse(input: TokenStream) -> syn::Result<(TokenStream, LitStr)> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+
+    let comma = tokens
+        .iter()
+        .rposition(|token| matches!(token, TokenTree::Punct(punct) if punct.as_char() == ','));
+
+    let (krate, path) = match comma {
+        Some(comma) => (
+            tokens[..comma].iter().cloned().collect(),
+            tokens[comma + 1..].iter().cloned().collect(),
+        ),
+        None => (quote!(::pillow_fs), tokens.into_iter().collect()),
+    };
+
+    Ok((krate, syn::parse2(path)?))
+}
+
+pub(crate) fn generate(krate: TokenStream, path: LitStr) -> syn::Result<TokenStream> {
+    let manifest = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let root = Path::new(&manifest).join(path.value());
+
+    let mut files = Vec::new();
+    walk(&root, &root, &mut files)
+        .map_err(|err| syn::Error::new(path.span(), format!("{}: {err}", root.display())))?;
+
+    files.sort();
+
+    let mut embedded = Vec::new();
+
+    for (relative, absolute) in files {
+        let content = fs::read(&absolute).map_err(|err| {
+            syn::Error::new(path.span(), format!("{}: {err}", absolute.display()))
+        })?;
+
+        let content_type = mime_guess::from_path(&absolute)
+            .first_or_octet_stream()
+            .to_string();
+        let etag = format!(
+            "\"{}\"",
+            URL_SAFE_NO_PAD.encode(Sha1::new_with_prefix(&content).finalize())
+        );
+        let absolute = absolute.to_string_lossy().to_string();
+
+        // include_bytes! rebuilds the crate when the file changes, the directory
+        // needs `cargo:rerun-if-changed` since proc_macro::tracked_path is unstable
+        embedded.push(quote! {
+            #krate::embed::EmbeddedFile::new(#relative, include_bytes!(#absolute), #content_type, #etag)
+        });
+    }
+
+    Ok(quote! {
+        {
+            const DIR: #krate::embed::EmbeddedDir =
+                #krate::embed::EmbeddedDir::new(#path, &[#(#embedded),*]);
+            DIR
+        }
+    })
+}
+
+/// Files under a directory, with their path relative to the root
+fn walk(root: &Path, directory: &Path, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            walk(root, &path, files)?;
+        } else if path.is_file() {
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            files.push((relative, path));
+        }
+    }
+
+    Ok(())
+}
@@ -5,6 +5,7 @@ use proc_macro::TokenStream;
 use syn::{parse_macro_input, AttributeArgs, Expr};
 
 mod controller;
+mod embed_dir;
 mod route;
 
 /// Conver controller in route
@@ -46,3 +47,15 @@ pub fn route(input: TokenStream) -> TokenStream {
 
     route::generate(input)
 }
+
+/// Bake a directory into the binary, used through `pillow_fs::embed_dir!`
+///
+/// Only the files are tracked by `include_bytes!`, a build script with
+/// `cargo:rerun-if-changed` tracks the directory
+#[proc_macro]
+pub fn embed_dir(input: TokenStream) -> TokenStream {
+    embed_dir::parse(input.into())
+        .and_then(|(krate, path)| embed_dir::generate(krate, path))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
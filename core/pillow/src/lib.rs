@@ -56,6 +56,9 @@ pub mod http {
     #[cfg(feature = "server")]
     pub use pillow_server::Server;
 
+    #[cfg(feature = "server")]
+    pub use pillow_server::ServerBuilder;
+
     #[cfg(feature = "routing")]
     pub use pillow_routing::Route;
 
@@ -64,11 +67,17 @@ pub mod http {
 
     #[cfg(feature = "macros")]
     pub use pillow_macros::route;
+
+    pub use pillow_http::embed_dir;
+    pub use pillow_http::static_dir::EmbeddedDir;
 }
 
 #[cfg(feature = "http")]
 pub use pillow_http::json;
 
+#[cfg(feature = "http")]
+pub use pillow_http::embed_dir;
+
 #[cfg(feature = "templates")]
 pub use pillow_templates as templates;
 
@@ -8,6 +8,8 @@
 
 mod route;
 mod router;
+mod websocket_route;
 
 pub use route::Route;
 pub use router::MainRouter;
+pub use websocket_route::WebSocketRoute;
@@ -1,9 +1,15 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, future::Future, path::Path};
 
-use crate::route::Route;
+use crate::{route::Route, websocket_route::WebSocketRoute};
 
 #[allow(unused_imports)]
-use pillow_http::{handler::Handler, middlewares::Middleware, Request, Response};
+use pillow_http::{
+    handler::Handler,
+    middlewares::Middleware,
+    static_dir::{EmbeddedDir, StaticDir},
+    websocket::WebSocket,
+    Request, Response,
+};
 
 /// The Main router in your app
 ///
@@ -17,6 +23,13 @@ use pillow_http::{handler::Handler, middlewares::Middleware, Request, Response};
 #[derive(Debug)]
 pub struct MainRouter {
     routes: HashMap<pillow_http::http_methods::HttpMethods, Vec<Route>>,
+    websocket_routes: Vec<WebSocketRoute>,
+
+    /// Directories served at request time
+    static_dirs: Vec<StaticDir>,
+
+    /// Cache-Control of the static files of each directory
+    cache_control: Vec<(String, String)>,
 }
 
 impl MainRouter {
@@ -24,6 +37,9 @@ impl MainRouter {
     pub fn new() -> Self {
         Self {
             routes: HashMap::new(),
+            websocket_routes: Vec::new(),
+            static_dirs: Vec::new(),
+            cache_control: Vec::new(),
         }
     }
 
@@ -32,6 +48,22 @@ impl MainRouter {
         &self.routes
     }
 
+    /// Reference of WebSocket routes
+    pub fn websocket_routes(&self) -> &Vec<WebSocketRoute> {
+        &self.websocket_routes
+    }
+
+    /// WebSocket route of an upgrade request
+    pub fn websocket_route(&self, request: &Request) -> Option<&WebSocketRoute> {
+        if !pillow_http::websocket::is_upgrade(request) {
+            return None;
+        }
+
+        self.websocket_routes
+            .iter()
+            .find(|route| route.uri() == request.uri())
+    }
+
     fn get_routes_from_method(
         &self,
         method: &pillow_http::http_methods::HttpMethods,
@@ -87,6 +119,14 @@ impl MainRouter {
         response.clone()
     }
 
+    /// File of a served directory, or 404
+    fn not_found(&self, response: &mut Response, request: &Request) -> Response {
+        self.static_dirs
+            .iter()
+            .find_map(|dir| dir.response(request))
+            .unwrap_or_else(|| self.response_404(response, request))
+    }
+
     fn use_controller(&self, route: &Route, request: &Request) -> Response {
         let response = route.use_controller(&request);
 
@@ -120,7 +160,7 @@ impl MainRouter {
 
                 response = self.use_controller(route, &request);
             } else {
-                return self.response_404(&mut response, &request);
+                return self.not_found(&mut response, &request);
             }
         }
 
@@ -140,7 +180,7 @@ impl MainRouter {
         match option_routes_vec {
             Some(routes) => routes_vec = routes,
             // Return 404
-            None => return vec![self.response_404(&mut response, &request)],
+            None => return vec![self.not_found(&mut response, &request)],
         }
 
         let option_index = self.get_option_index(request.uri(), &routes_vec);
@@ -161,7 +201,7 @@ impl MainRouter {
 
                 // IF not have params
                 if routes_params.len() == 0 {
-                    return vec![self.response_404(&mut response, &request)];
+                    return vec![self.not_found(&mut response, &request)];
                 }
 
                 // Search route have the url
@@ -223,6 +263,42 @@ impl MainRouter {
             .push(route)
     }
 
+    /// Add a WebSocket route
+    ///
+    /// The server answers the upgrade request and gives the connection to the handler
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of route
+    /// * `handler` - Async function with the WebSocket
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::websocket::WebSocket;
+    /// use pillow_routing::MainRouter;
+    ///
+    /// let mut router = MainRouter::new();
+    ///
+    /// router
+    ///     .websocket("/chat", |mut ws: WebSocket| async move {
+    ///         while let Ok(Some(message)) = ws.recv().await {
+    ///             let _ = ws.send(message).await;
+    ///         }
+    ///     })
+    ///     .protocols(&["chat"]);
+    /// ```
+    pub fn websocket<F, Fut>(&mut self, path: &str, handler: F) -> &mut WebSocketRoute
+    where
+        F: Fn(WebSocket) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.websocket_routes
+            .push(WebSocketRoute::new(path.to_string(), handler));
+
+        self.websocket_routes.last_mut().unwrap()
+    }
+
     /// Add files from the public directory
     ///
     /// ```rust
@@ -236,7 +312,7 @@ impl MainRouter {
     ///
     /// ```
     pub fn public(&mut self) {
-        self.static_files("public");
+        self.serve_dir("/public", "public");
     }
 
     /// Add assets from the resources directory
@@ -252,36 +328,164 @@ impl MainRouter {
     ///
     /// ```
     pub fn assets(&mut self) {
-        self.static_files("resources/js");
-        self.static_files("resources/css");
+        self.serve_dir("/resources/js", "resources/js");
+        self.serve_dir("/resources/css", "resources/css");
     }
 
-    /// Insert static files in the routes
+    /// Serve a directory under a path, its files are looked for on each request
+    ///
+    /// The routes come first, the directories answer the requests without route.
+    /// The Cache-Control set with `cache_control` apply to the directory
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Path of the directory in the urls
+    /// * `directory` - Directory of the files
+    ///
+    /// # Examples
     ///
     /// ```rust
-    /// fn (&mut self) {
-    ///     self.static_files("static");
-    /// }
+    /// use pillow_routing::MainRouter;
+    ///
+    /// let mut router = MainRouter::new();
+    ///
+    /// router.serve_dir("/static", "public").listing(true).cache(32 * 1024 * 1024);
     /// ```
-    fn static_files(&mut self, path: &str) {
-        let static_files = pillow_http::static_files::StaticFiles::new(path);
+    pub fn serve_dir(&mut self, prefix: &str, directory: &str) -> &mut StaticDir {
+        self.mount(StaticDir::new(prefix, directory))
+    }
 
-        for file in static_files.files {
-            let path = file.path.clone();
-            let content_type = Arc::new(file.content_type().unwrap().to_owned());
+    /// Serve a directory baked into the binary with `embed_dir!`
+    ///
+    /// The Cache-Control set with `cache_control` apply to the path given to `embed_dir!`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_http::static_dir::EmbeddedDir;
+    /// use pillow_routing::MainRouter;
+    ///
+    /// static PUBLIC: EmbeddedDir = pillow_http::embed_dir!("src");
+    ///
+    /// let mut router = MainRouter::new();
+    ///
+    /// router.serve_embedded("/static", PUBLIC);
+    /// ```
+    pub fn serve_embedded(&mut self, prefix: &str, directory: EmbeddedDir) -> &mut StaticDir {
+        self.mount(StaticDir::embedded(prefix, directory))
+    }
+
+    fn mount(&mut self, mut dir: StaticDir) -> &mut StaticDir {
+        for (directory, value) in &self.cache_control {
+            apply_cache_control(&mut dir, &self.cache_control, directory, value);
+        }
+
+        self.static_dirs.push(dir);
+        self.static_dirs.last_mut().unwrap()
+    }
+
+    /// Reference of the served directories
+    pub fn static_dirs(&self) -> &Vec<StaticDir> {
+        &self.static_dirs
+    }
 
-            let decoded_bytes = file.clone().content;
+    /// Cache-Control of the static files of a directory and its subdirectories
+    ///
+    /// The most specific directory wins, the directories already served get it too
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_routing::MainRouter;
+    ///
+    /// let mut router = MainRouter::new();
+    ///
+    /// router
+    ///     .cache_control("public", "no-cache")
+    ///     .cache_control("public/assets", "public, max-age=31536000, immutable");
+    /// router.public();
+    /// ```
+    pub fn cache_control(&mut self, directory: &str, cache_control: &str) -> &mut Self {
+        let directory = directory.trim_end_matches('/').to_string();
 
-            let closure = move |_: &Request| -> Response {
-                let content_type =
-                    pillow_http::header::from_str_ext_to_content_type(&content_type.clone());
+        self.cache_control.retain(|(dir, _)| *dir != directory);
+        self.cache_control
+            .push((directory.clone(), cache_control.to_string()));
 
-                let content = decoded_bytes.clone();
+        for dir in &mut self.static_dirs {
+            apply_cache_control(dir, &self.cache_control, &directory, cache_control);
+        }
 
-                Response::file(content_type, content.clone())
-            };
+        self
+    }
+}
 
-            self.add_route_closure(pillow_http::http_methods::HttpMethods::GET, &path, closure);
+/// Give a Cache-Control rule of the router to a served directory
+///
+/// A rule of a parent applies to the whole directory unless a closer parent has one
+fn apply_cache_control(
+    dir: &mut StaticDir,
+    rules: &[(String, String)],
+    directory: &str,
+    value: &str,
+) {
+    let root = dir.root().to_path_buf();
+    let directory = Path::new(directory);
+
+    if let Ok(subdirectory) = directory.strip_prefix(&root) {
+        dir.cache_control(&subdirectory.to_string_lossy(), value);
+    } else if root.starts_with(directory) {
+        let closer = rules.iter().any(|(parent, _)| {
+            let parent = Path::new(parent);
+
+            root.starts_with(parent) && parent.components().count() > directory.components().count()
+        });
+
+        if !closer {
+            dir.cache_control("", value);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pillow_http::header::Header;
+
+    #[test]
+    fn it_applies_the_cache_control_to_the_served_directories() {
+        let root = std::env::temp_dir().join(format!("pillow-router-cache-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(root.join("assets/app.js"), "app").unwrap();
+        std::fs::write(root.join("index.html"), "home").unwrap();
+
+        let directory = root.to_string_lossy().to_string();
+
+        let mut router = MainRouter::new();
+        router.public();
+        router.serve_dir("/static", &directory);
+        router
+            .cache_control(&format!("{directory}/assets"), "immutable")
+            .cache_control(&directory, "no-cache");
+
+        let cache_control = |uri: &str| {
+            let request = format!("GET {uri} HTTP/1.1\r\n\r\n");
+            let request = Request::from_vec(&request.into_bytes()).unwrap();
+
+            router.routing(&request)[0]
+                .get_header(&Header::CacheControl)
+                .map(str::to_string)
+        };
+
+        assert_eq!(
+            cache_control("/static/assets/app.js").as_deref(),
+            Some("immutable")
+        );
+        assert_eq!(
+            cache_control("/static/index.html").as_deref(),
+            Some("no-cache")
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use std::{fmt::Debug, future::Future, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use pillow_http::{
+    websocket::{self, WebSocket, WebSocketConfig},
+    Request,
+};
+
+type WebSocketHandler = Arc<dyn Fn(WebSocket) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Route of a WebSocket
+pub struct WebSocketRoute {
+    /// uri path
+    uri: pillow_http::Uri,
+
+    /// Subprotocols of the route, in order of preference of the client
+    protocols: Vec<String>,
+
+    config: WebSocketConfig,
+
+    /// Handler of the upgraded connection
+    handler: WebSocketHandler,
+}
+
+impl Debug for WebSocketRoute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebSocketRoute")
+            .field("uri", &self.uri)
+            .field("protocols", &self.protocols)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl WebSocketRoute {
+    /// New instance of WebSocketRoute
+    ///
+    /// # Arguments
+    ///
+    /// * `uri` - Path of the route
+    /// * `handler` - Async function with the WebSocket
+    pub fn new<F, Fut>(uri: String, handler: F) -> Self
+    where
+        F: Fn(WebSocket) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            uri: pillow_http::Uri(uri),
+            protocols: Vec::new(),
+            config: WebSocketConfig::default(),
+            handler: Arc::new(move |ws| Box::pin(handler(ws))),
+        }
+    }
+
+    /// Subprotocols accepted by the route
+    pub fn protocols(&mut self, protocols: &[&str]) -> &mut Self {
+        self.protocols = protocols
+            .iter()
+            .map(|protocol| protocol.to_string())
+            .collect();
+        self
+    }
+
+    /// Limits of the messages and auto-pong
+    pub fn config(&mut self, config: WebSocketConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    pub fn uri(&self) -> &pillow_http::Uri {
+        &self.uri
+    }
+
+    pub fn get_protocols(&self) -> &Vec<String> {
+        &self.protocols
+    }
+
+    pub fn get_config(&self) -> &WebSocketConfig {
+        &self.config
+    }
+
+    /// Subprotocol of the request accepted by the route
+    pub fn negotiate_protocol(&self, request: &Request) -> Option<String> {
+        websocket::negotiate_protocol(request, &self.protocols)
+    }
+
+    /// Run the handler until the WebSocket ends
+    pub fn handle(&self, ws: WebSocket) -> BoxFuture<'static, ()> {
+        (self.handler)(ws)
+    }
+}
@@ -0,0 +1,755 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use pillow_config::{Address, Configuration, Hsts, ListenerConfig, Ssl, SslRedirect};
+use pillow_http::compression::Compression;
+use pillow_ssl::TlsAcceptor;
+
+use crate::{server_http::ListenerSpec, Server};
+
+/// Connection limits of the Server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Pending connections queue of the listener
+    pub backlog: u32,
+
+    /// Max size in bytes of a request, bigger requests get a 413
+    pub max_request_size: usize,
+
+    /// Max open connections of the server, new connections get a 503
+    pub max_connections: Option<usize>,
+
+    /// Max open connections of a client IP, new connections get a 503
+    pub max_connections_per_ip: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            backlog: 1024,
+            max_request_size: 1024 * 1024,
+            max_connections: None,
+            max_connections_per_ip: None,
+        }
+    }
+}
+
+/// Connection timeouts of the Server
+///
+/// `None` waits forever
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Max time to read the headers of a request, a slow client gets a 408
+    pub header_read: Option<Duration>,
+
+    /// Max time to read the body of a request, a slow client gets a 408
+    pub body_read: Option<Duration>,
+
+    /// Max time to write a response
+    pub write: Option<Duration>,
+
+    /// Max idle time between two requests of a connection
+    pub keep_alive: Option<Duration>,
+
+    /// Max time of the TLS handshake
+    pub tls_handshake: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            header_read: Some(Duration::from_secs(30)),
+            body_read: Some(Duration::from_secs(60)),
+            write: Some(Duration::from_secs(60)),
+            keep_alive: Some(Duration::from_secs(5)),
+            tls_handshake: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// What to do when the port is in use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PortPolicy {
+    /// Fail with the bind error
+    #[default]
+    Exact,
+
+    /// Try the next ports, up to the number of attempts
+    NextFree(u16),
+}
+
+/// Unix domain socket to listen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixSocket {
+    /// Path of the socket file
+    pub path: PathBuf,
+
+    /// Permissions of the socket file, like `0o660`
+    pub mode: Option<u32>,
+
+    /// Remove the socket file left by a server that is not running
+    pub remove_stale: bool,
+}
+
+impl UnixSocket {
+    /// Instance of UnixSocket removing stale socket files
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            mode: None,
+            remove_stale: true,
+        }
+    }
+}
+
+/// Builder of Server
+///
+/// Values set in code have priority over the values of the configuration
+/// file, and the configuration file over the defaults.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pillow_server::ServerBuilder;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let server = ServerBuilder::new()
+///         .address([127, 0, 0, 1])
+///         .port(5000)
+///         .build()
+///         .unwrap();
+/// }
+/// ```
+#[derive(Default)]
+pub struct ServerBuilder {
+    config_file: Option<PathBuf>,
+    config: Option<Configuration>,
+
+    address: Option<Address>,
+    port: Option<u16>,
+    port_policy: Option<PortPolicy>,
+    listeners: Vec<ListenerConfig>,
+    unix_socket: Option<UnixSocket>,
+
+    ssl: Option<Ssl>,
+    tls_acceptor: Option<TlsAcceptor>,
+    redirect_http: Option<SslRedirect>,
+    hsts: Option<Hsts>,
+
+    compression: Option<Compression>,
+
+    backlog: Option<u32>,
+    max_request_size: Option<usize>,
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+
+    header_read_timeout: Option<Duration>,
+    body_read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+    tls_handshake_timeout: Option<Duration>,
+}
+
+impl ServerBuilder {
+    /// Instance of ServerBuilder without configuration file
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merge a configuration file like pillow.toml, a missing file is ignored
+    pub fn config_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Merge a configuration already loaded
+    pub fn config(mut self, config: Configuration) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Address to listen: IPv4, IPv6 or a hostname
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_server::ServerBuilder;
+    ///
+    /// let v4 = ServerBuilder::new().address([127, 0, 0, 1]);
+    /// let v6 = ServerBuilder::new().address("::1");
+    /// let host = ServerBuilder::new().address("localhost");
+    /// ```
+    pub fn address<A: Into<Address>>(mut self, address: A) -> Self {
+        self.address = Some(address.into());
+        self
+    }
+
+    /// Port to listen, 0 lets the system choose a free port
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// What to do when the port is in use
+    pub fn port_policy(mut self, policy: PortPolicy) -> Self {
+        self.port_policy = Some(policy);
+        self
+    }
+
+    /// Serve with TLS using a PEM certificate and a PEM PKCS #8 key
+    pub fn tls<C: Into<String>, K: Into<String>>(self, cert: C, key: K) -> Self {
+        self.ssl(Ssl {
+            cert: cert.into(),
+            key: key.into(),
+            ..Ssl::default()
+        })
+    }
+
+    /// Serve with TLS using a ssl configuration
+    pub fn ssl(mut self, ssl: Ssl) -> Self {
+        self.ssl = Some(ssl);
+        self
+    }
+
+    /// Serve with TLS using an acceptor already created
+    pub fn tls_acceptor(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls_acceptor = Some(acceptor);
+        self
+    }
+
+    /// Also listen on plain http and redirect every request to https
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_server::{ServerBuilder, SslRedirect};
+    ///
+    /// let builder = ServerBuilder::new()
+    ///     .port(443)
+    ///     .tls("cert.pem", "key.pem")
+    ///     .redirect_http(SslRedirect {
+    ///         port: Some(80),
+    ///         status: Some(308),
+    ///     });
+    /// ```
+    pub fn redirect_http(mut self, redirect: SslRedirect) -> Self {
+        self.redirect_http = Some(redirect);
+        self
+    }
+
+    /// Send Strict-Transport-Security in the https responses
+    pub fn hsts(mut self, hsts: Hsts) -> Self {
+        self.hsts = Some(hsts);
+        self
+    }
+
+    /// Compress the responses with the encodings accepted by the clients
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_http::compression::Compression;
+    /// use pillow_server::ServerBuilder;
+    ///
+    /// let builder = ServerBuilder::new().compression(Compression::default());
+    /// ```
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Add a listener with its own address, port and TLS settings
+    ///
+    /// When there are listeners, `address`, `port` and `tls` are not used
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_server::{ListenerConfig, ServerBuilder};
+    ///
+    /// let builder = ServerBuilder::new()
+    ///     .listener(ListenerConfig {
+    ///         address: "::".into(),
+    ///         port: 8080,
+    ///         ssl: None,
+    ///     })
+    ///     .listener(ListenerConfig {
+    ///         address: "::".into(),
+    ///         port: 8443,
+    ///         ssl: Some(pillow_server::Ssl {
+    ///             cert: "cert.pem".to_string(),
+    ///             key: "key.pem".to_string(),
+    ///             ..Default::default()
+    ///         }),
+    ///     });
+    /// ```
+    pub fn listener(mut self, listener: ListenerConfig) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Listen on a Unix domain socket instead of `address` and `port`
+    ///
+    /// The listeners added with `listener` are still used.
+    /// The socket file is removed on shutdown.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_server::{ServerBuilder, UnixSocket};
+    ///
+    /// let builder = ServerBuilder::new().unix_socket(UnixSocket {
+    ///     mode: Some(0o660),
+    ///     ..UnixSocket::new("/run/pillow.sock")
+    /// });
+    /// ```
+    pub fn unix_socket(mut self, unix_socket: UnixSocket) -> Self {
+        self.unix_socket = Some(unix_socket);
+        self
+    }
+
+    /// Pending connections queue of the listener
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.backlog = Some(backlog);
+        self
+    }
+
+    /// Max size in bytes of a request
+    pub fn max_request_size(mut self, size: usize) -> Self {
+        self.max_request_size = Some(size);
+        self
+    }
+
+    /// Max open connections of the server
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Max open connections of a client IP
+    pub fn max_connections_per_ip(mut self, max: usize) -> Self {
+        self.max_connections_per_ip = Some(max);
+        self
+    }
+
+    /// Max time to read the headers and the body of a request
+    pub fn read_timeout(self, timeout: Duration) -> Self {
+        self.header_read_timeout(timeout).body_read_timeout(timeout)
+    }
+
+    /// Max time to read the headers of a request
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.header_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Max time to read the body of a request
+    pub fn body_read_timeout(mut self, timeout: Duration) -> Self {
+        self.body_read_timeout = Some(timeout);
+        self
+    }
+
+    /// Max time to write a response
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Max idle time between two requests of a connection
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Max time of the TLS handshake
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.tls_handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Create the Server and bind its socket
+    pub fn build(self) -> io::Result<Server> {
+        let config = match (self.config, self.config_file) {
+            (Some(config), _) => Some(config),
+            (None, Some(path)) => pillow_config::read_config(path)?,
+            (None, None) => None,
+        };
+        let debug = match &config {
+            Some(config) => config.debug(),
+            None => true,
+        };
+        let config = config.map(|c| c.server()).unwrap_or_default();
+
+        let default_limits = Limits::default();
+        let config_limits = config.limits.clone().unwrap_or_default();
+        let limits = Limits {
+            backlog: self
+                .backlog
+                .or(config_limits.backlog)
+                .unwrap_or(default_limits.backlog),
+            max_request_size: self
+                .max_request_size
+                .or(config_limits.max_request_size)
+                .unwrap_or(default_limits.max_request_size),
+            max_connections: self.max_connections.or(config_limits.max_connections),
+            max_connections_per_ip: self
+                .max_connections_per_ip
+                .or(config_limits.max_connections_per_ip),
+        };
+
+        let default_timeouts = Timeouts::default();
+        let config_timeouts = config.timeouts.clone().unwrap_or_default();
+        let millis = |value: Option<u64>| value.map(Duration::from_millis);
+        let timeouts = Timeouts {
+            header_read: self
+                .header_read_timeout
+                .or(millis(config_timeouts.header_read))
+                .or(millis(config_timeouts.read))
+                .or(default_timeouts.header_read),
+            body_read: self
+                .body_read_timeout
+                .or(millis(config_timeouts.body_read))
+                .or(millis(config_timeouts.read))
+                .or(default_timeouts.body_read),
+            write: self
+                .write_timeout
+                .or(millis(config_timeouts.write))
+                .or(default_timeouts.write),
+            keep_alive: self
+                .keep_alive_timeout
+                .or(millis(config_timeouts.keep_alive))
+                .or(default_timeouts.keep_alive),
+            tls_handshake: self
+                .tls_handshake_timeout
+                .or(millis(config_timeouts.tls_handshake))
+                .or(default_timeouts.tls_handshake),
+        };
+
+        let address = self.address.unwrap_or_else(|| config.address.clone());
+        let port = self.port.unwrap_or(config.port);
+        let port_policy = self
+            .port_policy
+            .or(config.try_next_port.map(PortPolicy::NextFree))
+            .unwrap_or_default();
+
+        let config_listeners = config.listeners.clone().unwrap_or_default();
+
+        let unix_socket = self.unix_socket.or_else(|| {
+            config.unix_socket.clone().map(|path| UnixSocket {
+                path: path.into(),
+                mode: config.unix_socket_mode,
+                remove_stale: config.unix_socket_remove_stale.unwrap_or(true),
+            })
+        });
+
+        let specs = if !self.listeners.is_empty() || !config_listeners.is_empty() {
+            let listeners = if self.listeners.is_empty() {
+                config_listeners
+            } else {
+                self.listeners
+            };
+
+            let mut specs = Vec::with_capacity(listeners.len());
+
+            for listener in listeners {
+                let socket_addr = listener.address.to_socket_addr(listener.port)?;
+
+                specs.push(match listener.ssl {
+                    Some(ssl) => {
+                        let ssl = dev_cert(ssl, debug)?;
+
+                        ListenerSpec {
+                            socket_addr,
+                            tls_acceptor: Some(TlsAcceptor::from_config(&ssl)?),
+                            redirect_http: ssl.redirect,
+                            hsts: ssl.hsts.map(|hsts| hsts.header_value()),
+                        }
+                    }
+                    None => ListenerSpec::new(socket_addr, None),
+                });
+            }
+
+            specs
+        } else if unix_socket.is_some() {
+            Vec::new()
+        } else {
+            let ssl = match self.ssl.or(config.ssl()) {
+                Some(ssl) => Some(dev_cert(ssl, debug)?),
+                None => None,
+            };
+
+            let tls_acceptor = match (self.tls_acceptor, &ssl) {
+                (Some(acceptor), _) => Some(acceptor),
+                (None, Some(ssl)) => Some(TlsAcceptor::from_config(ssl)?),
+                (None, None) => None,
+            };
+
+            let ssl = ssl.unwrap_or_default();
+            let mut spec = ListenerSpec::new(address.to_socket_addr(port)?, tls_acceptor);
+
+            if spec.tls_acceptor.is_some() {
+                spec.redirect_http = self.redirect_http.or(ssl.redirect);
+                spec.hsts = self.hsts.or(ssl.hsts).map(|hsts| hsts.header_value());
+            }
+
+            vec![spec]
+        };
+
+        Server::bind(
+            specs,
+            unix_socket,
+            port_policy,
+            limits,
+            timeouts,
+            self.compression,
+        )
+    }
+}
+
+/// Use the self-signed development certificate when `dev_cert` is on
+///
+/// # Arguments
+///
+/// * debug - `[app] debug`, the certificate is refused without it
+fn dev_cert(ssl: Ssl, debug: bool) -> io::Result<Ssl> {
+    if !ssl.dev_cert.unwrap_or(false) {
+        return Ok(ssl);
+    }
+
+    if !debug {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "dev_cert is only allowed with [app] debug = true",
+        ));
+    }
+
+    #[cfg(feature = "dev-cert")]
+    {
+        let dir = ssl
+            .dev_cert_dir
+            .as_deref()
+            .unwrap_or("target/pillow/dev-cert");
+        let (cert, key) = pillow_ssl::dev_certificate(dir)?;
+
+        Ok(Ssl {
+            cert: cert.to_string_lossy().into_owned(),
+            key: key.to_string_lossy().into_owned(),
+            ..ssl
+        })
+    }
+
+    #[cfg(not(feature = "dev-cert"))]
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "dev_cert needs the dev-cert feature of pillow_server",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_works_without_config_file() {
+        let server = ServerBuilder::new()
+            .config_file("does-not-exist.toml")
+            .port(0)
+            .max_request_size(4096)
+            .read_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        assert_eq!(server.addr(), &std::net::IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(server.limits().max_request_size, 4096);
+        assert_eq!(server.timeouts().header_read, Some(Duration::from_secs(5)));
+        assert_eq!(server.timeouts().body_read, Some(Duration::from_secs(5)));
+        assert_eq!(server.timeouts().write, Timeouts::default().write);
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_bound_port() {
+        let server = ServerBuilder::new().port(0).build().unwrap();
+
+        assert_ne!(server.local_addr().port(), 0);
+        assert_eq!(server.port(), &server.local_addr().port());
+    }
+
+    #[tokio::test]
+    async fn it_fails_when_the_port_is_in_use() {
+        let first = ServerBuilder::new().port(0).build().unwrap();
+        let port = first.local_addr().port();
+
+        let err = ServerBuilder::new().port(port).build().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        let next = ServerBuilder::new()
+            .port(port)
+            .port_policy(PortPolicy::NextFree(10))
+            .build()
+            .unwrap();
+        assert_ne!(next.local_addr().port(), port);
+    }
+
+    #[tokio::test]
+    async fn it_listens_on_multiple_addresses() {
+        let server = ServerBuilder::new()
+            .listener(ListenerConfig {
+                address: "127.0.0.1".into(),
+                port: 0,
+                ssl: None,
+            })
+            .listener(ListenerConfig {
+                address: "::1".into(),
+                port: 0,
+                ssl: None,
+            })
+            .build();
+
+        // The sandbox may not have IPv6
+        if let Ok(server) = server {
+            let addrs = server.local_addrs();
+
+            assert_eq!(addrs.len(), 2);
+            assert!(addrs[0].is_ipv4());
+            assert!(addrs[1].is_ipv6());
+        }
+    }
+
+    #[tokio::test]
+    async fn it_survives_a_failed_tls_handshake() {
+        use tokio::io::AsyncWriteExt;
+
+        let cert = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../examples/ssl/cert/cert.pem"
+        );
+        let key = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../examples/ssl/cert/key.pem"
+        );
+
+        let server = ServerBuilder::new()
+            .port(0)
+            .tls(cert, key)
+            .tls_handshake_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let addr = server.local_addr();
+        let stats = server.stats();
+
+        tokio::spawn(
+            server.run_with_shutdown(pillow_routing::MainRouter::new(), std::future::pending()),
+        );
+
+        let mut garbage = tokio::net::TcpStream::connect(addr).await.unwrap();
+        garbage.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let _silent = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let failures = |count: u64| {
+            let stats = stats.clone();
+
+            tokio::time::timeout(Duration::from_secs(10), async move {
+                while stats.tls_handshake_failures() < count {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+        };
+
+        failures(2).await.unwrap();
+        assert_eq!(stats.tls_handshake_failures(), 2);
+
+        // The listener still accepts and handshakes the next connections
+        let mut garbage = tokio::net::TcpStream::connect(addr).await.unwrap();
+        garbage.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        failures(3).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_refuses_the_dev_cert_without_debug() {
+        let path = std::env::temp_dir().join(format!("pillow-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[app]\nname = \"app\"\ndebug = false\n\n[server.ssl]\ncert = \"\"\nkey = \"\"\ndev_cert = true\n",
+        )
+        .unwrap();
+
+        let err = ServerBuilder::new()
+            .config_file(&path)
+            .port(0)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_redirects_http_to_https() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let cert = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../examples/ssl/cert/cert.pem"
+        );
+        let key = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../examples/ssl/cert/key.pem"
+        );
+
+        let server = ServerBuilder::new()
+            .port(0)
+            .tls(cert, key)
+            .redirect_http(SslRedirect {
+                port: Some(0),
+                status: Some(308),
+            })
+            .build()
+            .unwrap();
+        let addrs = server.local_addrs();
+        assert_eq!(addrs.len(), 2);
+
+        tokio::spawn(
+            server.run_with_shutdown(pillow_routing::MainRouter::new(), std::future::pending()),
+        );
+
+        let mut stream = tokio::net::TcpStream::connect(addrs[1]).await.unwrap();
+        stream
+            .write_all(
+                b"GET /login?next=%2F HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 308"));
+        assert!(response.contains(&format!(
+            "Location: https://example.com:{}/login?next=%2F\r\n",
+            addrs[0].port()
+        )));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn it_removes_the_unix_socket_on_shutdown() {
+        let path = std::env::temp_dir().join(format!("pillow-{}.sock", std::process::id()));
+
+        let server = ServerBuilder::new()
+            .unix_socket(UnixSocket {
+                mode: Some(0o600),
+                ..UnixSocket::new(&path)
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(server.unix_socket(), Some(path.as_path()));
+        assert!(path.exists());
+
+        server
+            .run_with_shutdown(pillow_routing::MainRouter::new(), async {})
+            .await;
+
+        assert!(!path.exists());
+    }
+}
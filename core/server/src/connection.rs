@@ -0,0 +1,462 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use pillow_http::{
+    compression::Compression,
+    header::Header,
+    http_methods::from_str_to_http_method,
+    status_code::{self, StatusCode},
+    PeerCertificate, Request, Response,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+
+use crate::{
+    builder::{Limits, Timeouts},
+    redirect::Redirect,
+};
+
+/// What a connection adds to its requests and responses
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConnectionInfo {
+    /// Server name sent with TLS SNI
+    pub(crate) server_name: Option<String>,
+
+    /// Client certificate verified with mutual TLS
+    pub(crate) peer_certificate: Option<PeerCertificate>,
+
+    /// Value of the Strict-Transport-Security header
+    pub(crate) hsts: Option<Arc<str>>,
+
+    /// Redirect every request to https instead of routing
+    pub(crate) redirect: Option<Redirect>,
+
+    /// Compression of the responses
+    pub(crate) compression: Option<Arc<Compression>>,
+}
+
+/// Why a request could not be read
+#[derive(Debug)]
+pub(crate) enum ReadError {
+    /// The client closed the connection before a new request
+    Closed,
+
+    /// The client was idle between two requests
+    Idle,
+
+    /// The headers or the body were not received in time
+    Timeout,
+
+    /// The request is bigger than `max_request_size`
+    TooLarge,
+
+    /// The request is not valid http
+    Invalid,
+
+    /// The http method is not supported
+    NotImplemented,
+
+    Io(io::Error),
+}
+
+impl ReadError {
+    /// Response to send before closing the connection
+    pub(crate) fn response(&self) -> Option<Response> {
+        let status = match self {
+            ReadError::Timeout => StatusCode::ClientError(status_code::ClientError::RequestTimeout),
+            ReadError::TooLarge => {
+                StatusCode::ClientError(status_code::ClientError::PayloadTooLarge)
+            }
+            ReadError::Invalid => StatusCode::ClientError(status_code::ClientError::BadRequest),
+            ReadError::NotImplemented => {
+                StatusCode::ServerError(status_code::ServerError::NotImplemented)
+            }
+            ReadError::Closed | ReadError::Idle | ReadError::Io(_) => return None,
+        };
+
+        Some(close_response(status))
+    }
+}
+
+/// Empty response that closes the connection
+pub(crate) fn close_response(status: StatusCode) -> Response {
+    let mut response = Response::new_empty();
+
+    response.set_status_code(status);
+    response.add_multiple_headers(vec![
+        (Header::Connection, "close".to_string()),
+        (Header::ContentLength, "0".to_string()),
+    ]);
+
+    response
+}
+
+/// Reads requests from a connection, keeping the bytes of pipelined requests
+pub(crate) struct RequestReader {
+    buffer: Vec<u8>,
+    limits: Limits,
+    timeouts: Timeouts,
+}
+
+impl RequestReader {
+    pub(crate) fn new(limits: Limits, timeouts: Timeouts) -> Self {
+        Self {
+            buffer: Vec::new(),
+            limits,
+            timeouts,
+        }
+    }
+
+    /// Bytes read after the last request, the start of an upgraded protocol
+    pub(crate) fn into_buffer(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// Read the next request
+    ///
+    /// # Arguments
+    ///
+    /// * stream - Connection
+    /// * keep_alive - The connection already served a request
+    pub(crate) async fn read<S>(
+        &mut self,
+        stream: &mut S,
+        keep_alive: bool,
+    ) -> Result<Request, ReadError>
+    where
+        S: AsyncRead + Unpin,
+    {
+        // Wait the first byte of a kept alive connection with the idle timeout
+        if keep_alive && self.buffer.is_empty() {
+            match timeout(self.timeouts.keep_alive, self.fill(stream)).await {
+                Some(Ok(0)) => return Err(ReadError::Closed),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err),
+                None => return Err(ReadError::Idle),
+            }
+        }
+
+        let head_len = match timeout(self.timeouts.header_read, self.read_head(stream)).await {
+            Some(result) => result?,
+            None => return Err(ReadError::Timeout),
+        };
+
+        let body_len = content_length(&self.buffer[..head_len])?;
+
+        let len = match head_len.checked_add(body_len) {
+            Some(len) if len <= self.limits.max_request_size => len,
+            _ => return Err(ReadError::TooLarge),
+        };
+
+        match timeout(self.timeouts.body_read, self.read_body(stream, len)).await {
+            Some(result) => result?,
+            None => return Err(ReadError::Timeout),
+        };
+
+        let data: Vec<u8> = self.buffer.drain(..len).collect();
+
+        Request::from_vec(&data).map_err(|_| ReadError::Invalid)
+    }
+
+    /// Read until the end of the headers and return its length
+    async fn read_head<S>(&mut self, stream: &mut S) -> Result<usize, ReadError>
+    where
+        S: AsyncRead + Unpin,
+    {
+        loop {
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+            let mut request = httparse::Request::new(&mut headers);
+
+            match request.parse(&self.buffer) {
+                Ok(httparse::Status::Complete(len)) => {
+                    if from_str_to_http_method(request.method.unwrap_or_default()).is_err() {
+                        return Err(ReadError::NotImplemented);
+                    }
+
+                    return Ok(len);
+                }
+
+                Ok(httparse::Status::Partial) => {}
+
+                Err(_) => return Err(ReadError::Invalid),
+            }
+
+            if self.buffer.len() >= self.limits.max_request_size {
+                return Err(ReadError::TooLarge);
+            }
+
+            if self.fill(stream).await? == 0 {
+                return match self.buffer.is_empty() {
+                    true => Err(ReadError::Closed),
+                    false => Err(ReadError::Invalid),
+                };
+            }
+        }
+    }
+
+    /// Read until the buffer has `len` bytes
+    async fn read_body<S>(&mut self, stream: &mut S, len: usize) -> Result<(), ReadError>
+    where
+        S: AsyncRead + Unpin,
+    {
+        while self.buffer.len() < len {
+            if self.fill(stream).await? == 0 {
+                return Err(ReadError::Invalid);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn fill<S>(&mut self, stream: &mut S) -> Result<usize, ReadError>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let mut chunk = [0; 4096];
+
+        let size = stream.read(&mut chunk).await.map_err(ReadError::Io)?;
+        self.buffer.extend_from_slice(&chunk[..size]);
+
+        Ok(size)
+    }
+}
+
+/// Content-Length of the request, 0 without it
+///
+/// The chunked bodies are not supported: a request with Transfer-Encoding is
+/// refused, and with Content-Length too it's invalid, so its body is never
+/// read as the next request. The Content-Length headers have to agree
+fn content_length(head: &[u8]) -> Result<usize, ReadError> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut request = httparse::Request::new(&mut headers);
+
+    request.parse(head).map_err(|_| ReadError::Invalid)?;
+
+    let header = |name: &'static str| {
+        request
+            .headers
+            .iter()
+            .filter(move |header| header.name.eq_ignore_ascii_case(name))
+    };
+
+    let transfer_encoding = header("transfer-encoding").next().is_some();
+    let mut lengths = header("content-length").peekable();
+
+    match (transfer_encoding, lengths.peek().is_some()) {
+        (true, true) => return Err(ReadError::Invalid),
+        (true, false) => return Err(ReadError::NotImplemented),
+        _ => {}
+    }
+
+    let mut length = None;
+
+    // Repeated values, like `Content-Length: 4, 4`, are the same length
+    for header in lengths {
+        let values = std::str::from_utf8(header.value).map_err(|_| ReadError::Invalid)?;
+
+        for value in values.split(',') {
+            let value: usize = value.trim().parse().map_err(|_| ReadError::Invalid)?;
+
+            if length.is_some_and(|length| length != value) {
+                return Err(ReadError::Invalid);
+            }
+
+            length = Some(value);
+        }
+    }
+
+    Ok(length.unwrap_or(0))
+}
+
+/// Keep the connection open after the response
+pub(crate) fn is_keep_alive(request: &Request) -> bool {
+    let connection = request
+        .headers()
+        .get(&Header::Connection)
+        .map(|value| value.to_lowercase());
+
+    match connection.as_deref() {
+        Some("close") => false,
+        Some("keep-alive") => true,
+        _ => request.version() == "HTTP/1.1",
+    }
+}
+
+async fn timeout<F: std::future::Future>(
+    duration: Option<Duration>,
+    future: F,
+) -> Option<F::Output> {
+    match duration {
+        Some(duration) => tokio::time::timeout(duration, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Global and per IP connection limits shared by all the listeners
+#[derive(Debug)]
+pub(crate) struct ConnectionLimiter {
+    semaphore: Option<Arc<Semaphore>>,
+    max_per_ip: Option<usize>,
+    per_ip: Mutex<HashMap<IpAddr, usize>>,
+}
+
+/// Connection allowed by the ConnectionLimiter, released on drop
+pub(crate) struct ConnectionPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+    ip: Option<IpAddr>,
+    limiter: Arc<ConnectionLimiter>,
+}
+
+impl ConnectionLimiter {
+    pub(crate) fn new(limits: &Limits) -> Self {
+        Self {
+            semaphore: limits
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max))),
+            max_per_ip: limits.max_connections_per_ip,
+            per_ip: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a connection slot, `None` when a limit is reached
+    ///
+    /// # Arguments
+    ///
+    /// * ip - Address of the client, `None` for Unix domain sockets
+    pub(crate) fn try_acquire(self: &Arc<Self>, ip: Option<IpAddr>) -> Option<ConnectionPermit> {
+        let permit = match &self.semaphore {
+            Some(semaphore) => Some(semaphore.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+
+        let ip = match (ip, self.max_per_ip) {
+            (Some(ip), Some(max)) => {
+                let mut per_ip = self.per_ip.lock().unwrap();
+                let count = per_ip.entry(ip).or_insert(0);
+
+                if *count >= max {
+                    return None;
+                }
+
+                *count += 1;
+                Some(ip)
+            }
+            _ => None,
+        };
+
+        Some(ConnectionPermit {
+            _permit: permit,
+            ip,
+            limiter: self.clone(),
+        })
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        if let Some(ip) = self.ip {
+            let mut per_ip = self.limiter.per_ip.lock().unwrap();
+
+            if let Some(count) = per_ip.get_mut(&ip) {
+                *count -= 1;
+
+                if *count == 0 {
+                    per_ip.remove(&ip);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_limits_connections_per_ip() {
+        let limiter = Arc::new(ConnectionLimiter::new(&Limits {
+            max_connections: Some(3),
+            max_connections_per_ip: Some(2),
+            ..Limits::default()
+        }));
+        let ip = IpAddr::from([127, 0, 0, 1]);
+
+        let first = limiter.try_acquire(Some(ip)).unwrap();
+        let _second = limiter.try_acquire(Some(ip)).unwrap();
+        assert!(limiter.try_acquire(Some(ip)).is_none());
+
+        let _other = limiter
+            .try_acquire(Some(IpAddr::from([10, 0, 0, 1])))
+            .unwrap();
+        assert!(limiter.try_acquire(None).is_none());
+
+        drop(first);
+        assert!(limiter.try_acquire(Some(ip)).is_some());
+    }
+
+    #[tokio::test]
+    async fn it_reads_pipelined_requests() {
+        let mut reader = RequestReader::new(Limits::default(), Timeouts::default());
+        let mut stream: &[u8] =
+            b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\ntextGET /next HTTP/1.1\r\n\r\n";
+
+        let first = reader.read(&mut stream, false).await.unwrap();
+        let second = reader.read(&mut stream, true).await.unwrap();
+
+        assert_eq!(first.uri().0, "/");
+        assert_eq!(second.uri().0, "/next");
+        assert!(matches!(
+            reader.read(&mut stream, true).await,
+            Err(ReadError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_rejects_ambiguous_body_lengths() {
+        let read = |request: &'static [u8]| async move {
+            let mut reader = RequestReader::new(Limits::default(), Timeouts::default());
+            let mut stream = request;
+
+            reader.read(&mut stream, false).await
+        };
+
+        assert!(matches!(
+            read(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\ntext\r\n0\r\n\r\n")
+                .await,
+            Err(ReadError::NotImplemented)
+        ));
+        assert!(matches!(
+            read(b"POST / HTTP/1.1\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\ntext")
+                .await,
+            Err(ReadError::Invalid)
+        ));
+        assert!(matches!(
+            read(b"POST / HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 0\r\n\r\ntext").await,
+            Err(ReadError::Invalid)
+        ));
+        assert!(
+            read(b"POST / HTTP/1.1\r\nContent-Length: 4\r\nContent-Length: 4, 4\r\n\r\ntext")
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_rejects_a_huge_content_length() {
+        let mut reader = RequestReader::new(Limits::default(), Timeouts::default());
+        let mut stream: &[u8] =
+            b"POST / HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\ntext";
+
+        assert!(matches!(
+            reader.read(&mut stream, false).await,
+            Err(ReadError::TooLarge)
+        ));
+    }
+}
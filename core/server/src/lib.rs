@@ -1,3 +1,15 @@
+mod builder;
+mod connection;
+mod redirect;
 mod server_http;
+mod stats;
+#[cfg(unix)]
+mod unix;
 
+pub use builder::{Limits, PortPolicy, ServerBuilder, Timeouts, UnixSocket};
 pub use server_http::Server;
+pub use stats::ServerStats;
+
+pub use pillow_config::{
+    Address, ClientAuth, Hsts, ListenerConfig, Ssl, SslCertificate, SslRedirect,
+};
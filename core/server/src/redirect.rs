@@ -0,0 +1,102 @@
+use std::{io, net::SocketAddr};
+
+use pillow_config::SslRedirect;
+use pillow_http::{
+    header::Header,
+    status_code::{Redirection, StatusCode},
+    Request, Response,
+};
+
+/// Plain http listener answering every request with a redirect to https
+#[derive(Debug, Clone)]
+pub(crate) struct Redirect {
+    /// Address of the https listener
+    https_addr: SocketAddr,
+
+    /// 301 or 308
+    status: Redirection,
+}
+
+impl Redirect {
+    /// Instance of Redirect to the https listener
+    ///
+    /// Fails when the status is not 301 or 308
+    pub(crate) fn new(https_addr: SocketAddr, config: &SslRedirect) -> io::Result<Self> {
+        let status = match config.status.unwrap_or(301) {
+            301 => Redirection::MovedPermantely,
+            308 => Redirection::PermanetRedirect,
+            status => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("The http redirect status must be 301 or 308, not {status}"),
+                ))
+            }
+        };
+
+        Ok(Self { https_addr, status })
+    }
+
+    /// Redirect response to the same path and query on https
+    pub(crate) fn response(&self, request: &Request) -> Response {
+        let mut response = Response::new_empty();
+
+        response.set_status_code(StatusCode::Redirection(self.status.clone()));
+        response.add_header(Header::Location, self.location(request));
+
+        response
+    }
+
+    /// https origin of the Host with the path and the query of the request
+    fn location(&self, request: &Request) -> String {
+        let host = match request.host() {
+            Some(host) => host.to_string(),
+            None => match self.https_addr {
+                SocketAddr::V4(addr) => addr.ip().to_string(),
+                SocketAddr::V6(addr) => format!("[{}]", addr.ip()),
+            },
+        };
+
+        let mut location = match self.https_addr.port() {
+            443 => format!("https://{}{}", host, request.uri().0),
+            port => format!("https://{}:{}{}", host, port, request.uri().0),
+        };
+
+        if let Some(query) = request.query() {
+            location.push('?');
+            location.push_str(query);
+        }
+
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_redirects_to_the_https_origin() {
+        let request = Request::from_vec(
+            &b"GET /users?page=2&sort=name HTTP/1.1\r\nHost: example.com:80\r\n\r\n".to_vec(),
+        )
+        .unwrap();
+
+        let redirect = Redirect::new(([0, 0, 0, 0], 443).into(), &SslRedirect::default()).unwrap();
+        assert_eq!(
+            redirect.location(&request),
+            "https://example.com/users?page=2&sort=name"
+        );
+
+        let redirect = Redirect::new(([0, 0, 0, 0], 8443).into(), &SslRedirect::default()).unwrap();
+        assert_eq!(
+            redirect.location(&request),
+            "https://example.com:8443/users?page=2&sort=name"
+        );
+
+        let config = SslRedirect {
+            status: Some(302),
+            ..SslRedirect::default()
+        };
+        assert!(Redirect::new(([0, 0, 0, 0], 443).into(), &config).is_err());
+    }
+}
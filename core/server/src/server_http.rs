@@ -1,38 +1,111 @@
+use futures_util::future::join_all;
 use std::{
-    net::SocketAddr,
+    future::Future,
+    net::{IpAddr, SocketAddr},
     sync::{atomic::AtomicBool, Arc},
+    time::Duration,
 };
 
-use pillow_http::Request;
+use futures_util::StreamExt;
+use pillow_config::SslRedirect;
+use pillow_http::{
+    compression::Compression, header::Header, http_methods::HttpMethods, status_code,
+    websocket::WebSocket, BodyResponse, PeerCertificate, Response,
+};
 use pillow_routing::MainRouter;
-use pillow_ssl::{TcpStream as TcpStreamTLS, TlsAcceptor};
+use pillow_ssl::TlsAcceptor;
+
+use crate::{
+    builder::{Limits, PortPolicy, ServerBuilder, Timeouts, UnixSocket},
+    connection::{
+        close_response, is_keep_alive, ConnectionInfo, ConnectionLimiter, ConnectionPermit,
+        ReadError, RequestReader,
+    },
+    redirect::Redirect,
+    stats::ServerStats,
+};
 
 use tokio::{
-    io::{AsyncWriteExt, Interest},
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
     net::TcpListener,
-    sync::{watch, Mutex, MutexGuard},
+    sync::watch,
 };
 
+#[cfg(unix)]
+use crate::unix::UnixSocketGuard;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
 /// Server for you app
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Server {
     state: watch::Sender<State>,
-    /// Address
-    addr: [u8; 4],
+    /// Address of the first TCP listener
+    addr: IpAddr,
 
-    /// Port on listen
+    /// Port on listen of the first TCP listener
     port: u16,
 
     socket_addr: SocketAddr,
 
-    listener: TcpListener,
+    listeners: Vec<BoundListener>,
+
+    limits: Limits,
 
-    tls_acceptor: Option<TlsAcceptor>,
+    timeouts: Timeouts,
+
+    compression: Option<Arc<Compression>>,
+
+    stats: Arc<ServerStats>,
 
     shutdown: Arc<AtomicBool>,
 }
 
+/// Address and TLS settings of a listener before binding it
+#[derive(Debug)]
+pub(crate) struct ListenerSpec {
+    pub(crate) socket_addr: SocketAddr,
+    pub(crate) tls_acceptor: Option<TlsAcceptor>,
+
+    /// Plain http listener redirecting to this https listener
+    pub(crate) redirect_http: Option<SslRedirect>,
+
+    /// Value of the Strict-Transport-Security header
+    pub(crate) hsts: Option<String>,
+}
+
+impl ListenerSpec {
+    pub(crate) fn new(socket_addr: SocketAddr, tls_acceptor: Option<TlsAcceptor>) -> Self {
+        Self {
+            socket_addr,
+            tls_acceptor,
+            redirect_http: None,
+            hsts: None,
+        }
+    }
+}
+
+/// Socket already bound
+#[derive(Debug)]
+enum BoundListener {
+    Tcp {
+        socket_addr: SocketAddr,
+        listener: TcpListener,
+        tls_acceptor: Option<TlsAcceptor>,
+        hsts: Option<String>,
+
+        /// Every request is redirected to https
+        redirect: Option<Redirect>,
+    },
+
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        guard: UnixSocketGuard,
+    },
+}
+
 #[derive(Debug, PartialEq, Eq)]
 #[allow(dead_code)]
 pub(crate) enum State {
@@ -58,11 +131,9 @@ impl std::default::Default for Server {
 }
 
 impl Server {
-    /// Instance of Server
-    ///
-    /// # Arguments
+    /// Instance of Server from pillow.toml
     ///
-    /// * port - port of your app
+    /// pillow.toml is optional, without it the defaults are used
     ///
     /// # Examples
     ///
@@ -71,52 +142,174 @@ impl Server {
     /// async fn main(){
     ///     let server = Server::new().unwrap();
     /// }
-    /// ```   
+    /// ```
     pub fn new() -> Result<Self, std::io::Error> {
-        let server_config = pillow_config::get_config().server();
-
-        let port = server_config.port;
-        let addr = server_config.address;
+        ServerBuilder::new().config_file("pillow.toml").build()
+    }
 
-        let tls = match server_config.ssl() {
-            Some(_) => Some(TlsAcceptor::new()),
-            None => None,
-        };
+    /// Builder to configure the Server in code
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_server::Server;
+    ///
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let server = Server::builder().port(5000).build().unwrap();
+    /// }
+    /// ```
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
 
+    pub(crate) fn bind(
+        specs: Vec<ListenerSpec>,
+        unix_socket: Option<UnixSocket>,
+        port_policy: PortPolicy,
+        limits: Limits,
+        timeouts: Timeouts,
+        compression: Option<Compression>,
+    ) -> Result<Self, std::io::Error> {
         let (state, _) = watch::channel(State::Starting);
-        let socket_addr = SocketAddr::from((addr, port.try_into().unwrap()));
 
-        let socket = tokio::net::TcpSocket::new_v4()?;
+        let mut listeners = Vec::with_capacity(specs.len() + 1);
 
-        #[cfg(not(win))]
-        socket.set_reuseaddr(true)?;
+        if let Some(unix_socket) = unix_socket {
+            listeners.push(Self::bind_unix(&unix_socket)?);
+        }
 
-        match socket.bind(socket_addr) {
-            Ok(_) => {}
-            Err(_) => {
-                let socket_addr = SocketAddr::from((addr, port + 1));
-                socket.bind(socket_addr).unwrap();
+        let mut first_socket_addr = None;
+
+        for spec in specs {
+            let listener = Self::bind_listener(spec.socket_addr, port_policy, limits.backlog)?;
+            let socket_addr = listener.local_addr()?;
+
+            first_socket_addr.get_or_insert(socket_addr);
+
+            listeners.push(BoundListener::Tcp {
+                socket_addr,
+                listener,
+                tls_acceptor: spec.tls_acceptor,
+                hsts: spec.hsts,
+                redirect: None,
+            });
+
+            if let Some(redirect_http) = spec.redirect_http {
+                let http_addr = SocketAddr::new(socket_addr.ip(), redirect_http.port.unwrap_or(80));
+                let listener = Self::bind_listener(http_addr, PortPolicy::Exact, limits.backlog)?;
+
+                listeners.push(BoundListener::Tcp {
+                    socket_addr: listener.local_addr()?,
+                    listener,
+                    tls_acceptor: None,
+                    hsts: None,
+                    redirect: Some(Redirect::new(socket_addr, &redirect_http)?),
+                });
             }
-        };
+        }
 
-        let listener = socket.listen(1024)?;
+        if listeners.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The server needs at least one listener",
+            ));
+        }
+
+        let socket_addr = first_socket_addr.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
 
         let shutdown = Arc::new(AtomicBool::new(false));
 
         Ok(Self {
             state,
-            addr,
-            port,
+            addr: socket_addr.ip(),
+            port: socket_addr.port(),
             socket_addr,
-            listener,
+            listeners,
             shutdown,
 
-            tls_acceptor: tls,
+            limits,
+            timeouts,
+            compression: compression.map(Arc::new),
+            stats: Arc::default(),
         })
     }
 
-    /// Reference of add
-    pub fn addr(&self) -> &[u8; 4] {
+    /// Bind a TcpListener following the PortPolicy
+    fn bind_listener(
+        socket_addr: SocketAddr,
+        port_policy: PortPolicy,
+        backlog: u32,
+    ) -> Result<TcpListener, std::io::Error> {
+        let port = socket_addr.port();
+
+        let attempts = match port_policy {
+            PortPolicy::Exact => 0,
+            // Port 0 is always free
+            PortPolicy::NextFree(_) if port == 0 => 0,
+            PortPolicy::NextFree(attempts) => attempts,
+        };
+
+        let mut next_addr = socket_addr;
+
+        loop {
+            match Self::bind_socket(next_addr, backlog) {
+                Ok(listener) => {
+                    if next_addr.port() != port {
+                        println!("Port {} is in use, using port {}", port, next_addr.port());
+                    }
+
+                    return Ok(listener);
+                }
+
+                Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => {
+                    match next_addr.port().checked_add(1) {
+                        Some(p) if p - port <= attempts => next_addr.set_port(p),
+                        _ => return Err(err),
+                    }
+                }
+
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn bind_unix(unix_socket: &UnixSocket) -> Result<BoundListener, std::io::Error> {
+        let (listener, guard) = crate::unix::bind(unix_socket)?;
+
+        Ok(BoundListener::Unix { listener, guard })
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_: &UnixSocket) -> Result<BoundListener, std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Unix domain sockets are not supported on this platform",
+        ))
+    }
+
+    fn bind_socket(socket_addr: SocketAddr, backlog: u32) -> Result<TcpListener, std::io::Error> {
+        let socket = match socket_addr {
+            SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+        };
+
+        #[cfg(not(windows))]
+        socket.set_reuseaddr(true)?;
+
+        socket.bind(socket_addr).map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("Could not bind {}: {}", socket_addr, err),
+            )
+        })?;
+
+        socket.listen(backlog)
+    }
+
+    /// Reference of addr
+    pub fn addr(&self) -> &IpAddr {
         &self.addr
     }
 
@@ -129,10 +322,59 @@ impl Server {
     pub fn socket_addr(&self) -> &SocketAddr {
         &self.socket_addr
     }
+
+    /// Address where the Server is listening
+    ///
+    /// With port 0 it's the port chosen by the system,
+    /// with only a Unix domain socket it's `0.0.0.0:0`
+    pub fn local_addr(&self) -> SocketAddr {
+        self.socket_addr
+    }
+
+    /// Addresses of all the TCP listeners
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| match listener {
+                BoundListener::Tcp { socket_addr, .. } => Some(*socket_addr),
+                #[cfg(unix)]
+                BoundListener::Unix { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Path of the Unix domain socket
+    #[cfg(unix)]
+    pub fn unix_socket(&self) -> Option<&std::path::Path> {
+        self.listeners.iter().find_map(|listener| match listener {
+            BoundListener::Unix { guard, .. } => Some(guard.path()),
+            BoundListener::Tcp { .. } => None,
+        })
+    }
+
+    /// Reference of limits
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Reference of timeouts
+    pub fn timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+
+    /// Compression of the responses, `None` when they are not compressed
+    pub fn compression(&self) -> Option<&Compression> {
+        self.compression.as_deref()
+    }
+
+    /// Counters of the Server, still updated while it runs
+    pub fn stats(&self) -> Arc<ServerStats> {
+        self.stats.clone()
+    }
 }
 
 impl Server {
-    /// Run you Server
+    /// Run you Server until Ctrl-C
     ///
     /// # Arguments
     ///
@@ -149,24 +391,163 @@ impl Server {
     /// }
     /// ```
     pub async fn run(self, router: MainRouter) {
-        let _url = pillow_config::get_config().server().url;
-        self.state.send_replace(State::Listening);
+        self.run_with_shutdown(router, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await;
+    }
 
-        println!("Listening on http://{}/", &self.socket_addr);
+    /// Run you Server until the signal future is completed
+    ///
+    /// The listeners are closed and the Unix domain socket is removed
+    /// when the signal is completed
+    ///
+    /// # Arguments
+    ///
+    /// * router - You MainRouter
+    /// * signal - Future to stop the Server
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_routing::MainRouter;
+    /// use pillow_server::Server;
+    ///
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let router = MainRouter::new();
+    ///     let server = Server::builder().port(0).build().unwrap();
+    ///     let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    ///
+    ///     tokio::spawn(server.run_with_shutdown(router, async {
+    ///         let _ = rx.await;
+    ///     }));
+    ///
+    ///     tx.send(()).unwrap();
+    /// }
+    /// ```
+    pub async fn run_with_shutdown<F>(self, router: MainRouter, signal: F)
+    where
+        F: Future<Output = ()>,
+    {
+        self.state.send_replace(State::Listening);
 
         let router = Arc::new(router);
+        let limiter = Arc::new(ConnectionLimiter::new(&self.limits));
+        let mut reload_tasks = Vec::new();
+
+        let listeners: Vec<Listener> = self
+            .listeners
+            .into_iter()
+            .map(|bound| {
+                let (kind, tls_acceptor, hsts, redirect) = match bound {
+                    BoundListener::Tcp {
+                        socket_addr,
+                        listener,
+                        tls_acceptor,
+                        hsts,
+                        redirect,
+                    } => {
+                        let scheme = match &tls_acceptor {
+                            Some(tls) => {
+                                match tls.start_reload() {
+                                    Ok(tasks) => reload_tasks.extend(tasks),
+                                    Err(err) => {
+                                        eprintln!("Could not watch the TLS certificates: {}", err)
+                                    }
+                                }
+
+                                "https"
+                            }
+                            None => "http",
+                        };
+                        match &redirect {
+                            Some(_) => println!("Redirecting http://{}/ to https", &socket_addr),
+                            None => println!("Listening on {}://{}/", scheme, &socket_addr),
+                        }
+
+                        (ListenerKind::Tcp(listener), tls_acceptor, hsts, redirect)
+                    }
+
+                    #[cfg(unix)]
+                    BoundListener::Unix { listener, guard } => {
+                        println!("Listening on unix:{}", guard.path().display());
+
+                        (
+                            ListenerKind::Unix {
+                                listener,
+                                _guard: guard,
+                            },
+                            None,
+                            None,
+                            None,
+                        )
+                    }
+                };
 
-        let listener = Listener::new(self.listener, router, self.tls_acceptor);
+                let mut listener = Listener::new(
+                    kind,
+                    router.clone(),
+                    limiter.clone(),
+                    self.stats.clone(),
+                    tls_acceptor,
+                    self.limits,
+                    self.timeouts,
+                );
+                listener.hsts = hsts.map(Arc::from);
+                listener.redirect = redirect;
+                listener.compression = self.compression.clone();
+
+                listener
+            })
+            .collect();
+
+        tokio::select! {
+            results = join_all(listeners.iter().map(|listener| listener.run())) => {
+                for result in results {
+                    if let Err(err) = result {
+                        eprintln!("Listener stopped: {}", err);
+                    }
+                }
+            }
 
-        listener.run().await.unwrap();
+            _ = signal => {
+                println!("Shutting down");
+            }
+        }
+
+        for task in reload_tasks {
+            task.abort();
+        }
+
+        self.state.send_replace(State::Shutdown);
     }
 }
 
+/// Socket accepting connections
+enum ListenerKind {
+    Tcp(TcpListener),
+
+    /// The guard removes the socket file when the listener is dropped
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        _guard: UnixSocketGuard,
+    },
+}
+
 /// Listener http
 struct Listener {
-    listener: TcpListener,
+    kind: ListenerKind,
     tls_acceptor: Option<Arc<TlsAcceptor>>,
+    hsts: Option<Arc<str>>,
+    redirect: Option<Redirect>,
+    compression: Option<Arc<Compression>>,
     router: Arc<MainRouter>,
+    limiter: Arc<ConnectionLimiter>,
+    stats: Arc<ServerStats>,
+    limits: Limits,
+    timeouts: Timeouts,
 }
 
 impl Listener {
@@ -174,24 +555,37 @@ impl Listener {
     ///
     /// # Arguments
     ///
-    /// * listener - TcpListener
-    pub fn new(listener: TcpListener, router: Arc<MainRouter>, tls: Option<TlsAcceptor>) -> Self {
+    /// * kind - TcpListener or UnixListener
+    /// * limiter - Connection limits shared by all the listeners
+    pub fn new(
+        kind: ListenerKind,
+        router: Arc<MainRouter>,
+        limiter: Arc<ConnectionLimiter>,
+        stats: Arc<ServerStats>,
+        tls: Option<TlsAcceptor>,
+        limits: Limits,
+        timeouts: Timeouts,
+    ) -> Self {
         Self {
-            listener,
+            kind,
             router,
-            tls_acceptor: match tls {
-                Some(ssl) => Some(Arc::new(ssl)),
-                None => None,
-            },
+            limiter,
+            stats,
+            limits,
+            timeouts,
+            tls_acceptor: tls.map(Arc::new),
+            hsts: None,
+            redirect: None,
+            compression: None,
         }
     }
 }
 
 impl Listener {
     pub async fn run<'a>(&'a self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'a>> {
-        match self.tls_acceptor.clone() {
-            Some(tls) => self.listen_with_tls(tls).await?,
-            None => self.listen().await?,
+        match (&self.kind, self.tls_acceptor.clone()) {
+            (ListenerKind::Tcp(listener), Some(tls)) => self.listen_with_tls(listener, tls).await?,
+            _ => self.listen().await?,
         };
 
         Ok(())
@@ -200,214 +594,369 @@ impl Listener {
     /// Listen Listener
     async fn listen<'a, 'b>(&'a self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'a>> {
         loop {
-            match self.listener.accept().await {
-                Ok((stream, _client)) => {
-                    let router_clone = self.router.clone();
-                    let shared_stream = Arc::new(Mutex::new(stream));
-
-                    // let shared_stream_clone = shared_stream.clone();
-
-                    tokio::task::spawn(async move {
-                        if let Err(err) =
-                            Self::handle_connections(shared_stream, &router_clone).await
-                        {
-                            eprintln!("{}", err);
-                        };
-                    });
+            match &self.kind {
+                ListenerKind::Tcp(listener) => match listener.accept().await {
+                    Ok((stream, client)) => self.spawn_connection(stream, Some(client.ip())),
+                    Err(err) => eprintln!("{}", err),
+                },
+
+                #[cfg(unix)]
+                ListenerKind::Unix { listener, .. } => match listener.accept().await {
+                    Ok((stream, _client)) => self.spawn_connection(stream, None),
+                    Err(err) => eprintln!("{}", err),
+                },
+            };
+        }
+    }
 
-                    /*
-                    tokio::spawn(async move {
-                        if let Err(err) = Self::websockets(shared_stream_clone).await {
-                            eprintln!("{}", err);
-                        };
-                    });
-                    */
+    /// Handle the connection in a new task
+    ///
+    /// Over the connection limits the client gets a 503
+    fn spawn_connection<S>(&self, stream: S, client: Option<IpAddr>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let router_clone = self.router.clone();
+        let info = ConnectionInfo {
+            redirect: self.redirect.clone(),
+            compression: self.compression.clone(),
+            ..ConnectionInfo::default()
+        };
+        let (limits, timeouts) = (self.limits, self.timeouts);
+        let permit = self.limiter.try_acquire(client);
+
+        tokio::task::spawn(async move {
+            let mut stream = stream;
+
+            let _permit: ConnectionPermit = match permit {
+                Some(permit) => permit,
+                None => {
+                    let response = close_response(status_code::StatusCode::ServerError(
+                        status_code::ServerError::ServiceUnavailable,
+                    ));
+                    let _ =
+                        Self::write_response(&mut stream, response, false, timeouts.write).await;
+                    return;
                 }
+            };
 
-                Err(err) => eprintln!("{}", err),
+            if let Err(err) =
+                Self::handle_connections(stream, &router_clone, info, limits, timeouts).await
+            {
+                eprintln!("{}", err);
             };
-        }
+        });
     }
 
+    /// Listen with TLS
+    ///
+    /// The handshake runs in the connection task, a failed handshake
+    /// is logged and counted without stopping the listener
     async fn listen_with_tls<'a>(
         &'a self,
+        listener: &'a TcpListener,
         tls: Arc<TlsAcceptor>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'a>> {
-        while let Ok((stream, _client_addr)) = self.listener.accept().await {
+        loop {
+            let (stream, client_addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    continue;
+                }
+            };
+
+            // Over the limits the connection is closed before the handshake
+            let Some(permit) = self.limiter.try_acquire(Some(client_addr.ip())) else {
+                continue;
+            };
+
             let tls = tls.clone();
-            let stream = tls.accept(stream).await?;
+            let stats = self.stats.clone();
             let router_clone = self.router.clone();
+            let hsts = self.hsts.clone();
+            let compression = self.compression.clone();
+            let (limits, timeouts) = (self.limits, self.timeouts);
 
             tokio::task::spawn(async move {
-                if let Err(err) = Self::handle_tls_connections(stream, &router_clone).await {
+                let _permit = permit;
+
+                let stream = match with_timeout(timeouts.tls_handshake, async {
+                    tls.accept(stream).await.map_err(std::io::Error::other)
+                })
+                .await
+                {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        stats.tls_handshake_failed();
+                        eprintln!("TLS handshake with {} failed: {}", client_addr, err);
+                        return;
+                    }
+                };
+
+                let info = ConnectionInfo {
+                    server_name: stream.server_name().map(String::from),
+                    peer_certificate: stream
+                        .peer_certificate()
+                        .and_then(|der| PeerCertificate::from_der(&der).ok()),
+                    hsts,
+                    redirect: None,
+                    compression,
+                };
+
+                if let Err(err) =
+                    Self::handle_connections(stream, &router_clone, info, limits, timeouts).await
+                {
                     eprintln!("{}", err);
                 };
             });
         }
-        Ok(())
-    }
-
-    /*
-    async fn websockets(
-        stream: Arc<Mutex<tokio::net::TcpStream>>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let ws = crate::ws::WS::new(stream);
-
-        ws.run().await?;
-
-        Ok(())
     }
-     */
 
     /// Handle new connections
     ///
+    /// The connection is kept alive between requests until the client
+    /// closes it, sends `Connection: close` or is idle for too long.
+    /// An upgrade request of a WebSocket route gives the connection
+    /// to the handler of the route
+    ///
     /// # Arguments
     ///
-    /// * stream - &TcpStream
+    /// * stream - TcpStream, TLS stream or UnixStream
     /// * router - &MainRouter
-    async fn handle_connections(
-        stream: Arc<Mutex<tokio::net::TcpStream>>,
-        // stream: &mut tokio::net::TcpStream,
+    /// * info - TLS details and settings of the connection
+    /// * limits - Limits of the Server
+    /// * timeouts - Timeouts of the Server
+    async fn handle_connections<S>(
+        mut stream: S,
         router: &MainRouter,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let stream = stream.lock().await;
+        info: ConnectionInfo,
+        limits: Limits,
+        timeouts: Timeouts,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let mut reader = RequestReader::new(limits, timeouts);
+        let mut keep_alive = false;
 
-        let ready_readable = stream.ready(Interest::READABLE).await?;
-        let ready_writable = stream.ready(Interest::WRITABLE).await?;
-
-        let mut request: Request = Request::new_empty();
+        loop {
+            let mut request = match reader.read(&mut stream, keep_alive).await {
+                Ok(request) => request,
+                Err(err) => {
+                    if let Some(response) = err.response() {
+                        Self::write_response(&mut stream, response, false, timeouts.write).await?;
+                    }
 
-        if ready_readable.is_readable() {
-            request = Self::read_stream(&stream);
-        };
+                    return match err {
+                        ReadError::Io(err) => Err(err.into()),
+                        _ => Ok(()),
+                    };
+                }
+            };
 
-        /*
-        if request.uri() == &Uri("/ws".to_string()) {
-            println!("ejecting");
-            let ws = accept_async(&mut *stream).await?;
-            println!(" ws is created");
+            keep_alive = is_keep_alive(&request);
+            request.set_server_name(info.server_name.clone());
+            request.set_peer_certificate(info.peer_certificate.clone());
+
+            if let Some(route) = router
+                .websocket_route(&request)
+                .filter(|_| info.redirect.is_none())
+            {
+                let protocol = route.negotiate_protocol(&request);
+                let response =
+                    Response::websocket_upgrade_connection(&request, protocol.as_deref());
+
+                if !matches!(
+                    response.get_status_code(),
+                    status_code::StatusCode::Information(_)
+                ) {
+                    Self::write_stream(
+                        &mut stream,
+                        vec![response],
+                        &info,
+                        false,
+                        false,
+                        timeouts.write,
+                    )
+                    .await?;
+
+                    return Ok(());
+                }
 
-            let (mut w, mut r) = ws.split();
+                Self::write_response(&mut stream, response, false, timeouts.write).await?;
 
-            while let Some(msg) = r.next().await {
-                let msg = msg?;
+                let ws = WebSocket::new(
+                    stream,
+                    reader.into_buffer(),
+                    request,
+                    protocol,
+                    *route.get_config(),
+                );
+                route.handle(ws).await;
 
-                match msg {
-                    tungstenite::Message::Text(text) => {
-                        println!("{:#?}", text);
-                        w.send(tungstenite::Message::Text(String::from("hello")))
-                            .await?;
-                    }
+                return Ok(());
+            }
 
-                    tungstenite::Message::Binary(b) => {
-                        println!("{:#?}", b);
-                    }
+            let mut responses = match &info.redirect {
+                Some(redirect) => vec![redirect.response(&request)],
+                None => router.routing(&request),
+            };
 
-                    _ => {}
-                }
+            if let Some(compression) = &info.compression {
+                responses = responses
+                    .into_iter()
+                    .map(|response| compression.compress(&request, response))
+                    .collect();
             }
 
-            return Ok(());
-        }
-         */
+            let head = *request.method() == HttpMethods::HEAD;
 
-        if ready_writable.is_writable() {
-            Self::write_stream(stream, &mut request, &router).await?;
-        };
+            Self::write_stream(
+                &mut stream,
+                responses,
+                &info,
+                keep_alive,
+                head,
+                timeouts.write,
+            )
+            .await?;
 
-        Ok(())
+            if !keep_alive {
+                return Ok(());
+            }
+        }
     }
 
-    async fn handle_tls_connections(
-        mut stream: TcpStreamTLS,
-        router: &MainRouter,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let request = Self::read_stream_tls(&mut stream).await;
+    /// Write the responses of a request in stream
+    ///
+    /// # Arguments
+    ///
+    /// * stream - TcpStream, TLS stream or UnixStream
+    /// * responses - Responses of the Router
+    /// * info - Settings of the connection
+    /// * keep_alive - The connection stays open after the response
+    /// * head - The request is a HEAD, the bodies are not sent
+    /// * timeout - Timeout of each write
+    async fn write_stream<S>(
+        stream: &mut S,
+        responses: Vec<Response>,
+        info: &ConnectionInfo,
+        keep_alive: bool,
+        head: bool,
+        timeout: Option<Duration>,
+    ) -> Result<(), std::io::Error>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        for mut response in responses {
+            if let Some(hsts) = &info.hsts {
+                response.add_header(Header::StrictTransportSecurity, hsts.to_string());
+            }
+
+            if !keep_alive {
+                response.add_header(Header::Connection, "close".to_string());
+            }
 
-        Self::write_stream_tls(stream, &request, router).await?;
+            Self::write_response(stream, response, head, timeout).await?;
+        }
 
         Ok(())
     }
 
-    /// Write data in stream
+    /// Write a Response, each write fails after the timeout
     ///
-    /// # Arguments
-    ///
-    /// * stream - tokio TcpStream
-    /// * request - for Router
-    /// * router - MainRouter
-    async fn write_stream<'a>(
-        // stream: &mut tokio::net::TcpStream,
-        mut stream: MutexGuard<'a, tokio::net::TcpStream>,
-        request: &Request,
-        router: &MainRouter,
-    ) -> Result<(), std::io::Error> {
-        let vec_response = router.routing(request);
-
-        for response in vec_response {
-            let headers = format!(
-                "{}{}\r\n\r\n",
-                response.get_status_line(),
-                response.get_headers()
-            );
-            let body = response.get_body();
-
-            stream.write_all(headers.as_bytes()).await?;
-            stream.write_all(body.as_bytes()).await?;
+    /// A streamed body is sent by chunks with chunked transfer encoding and
+    /// the other bodies with their Content-Length, informational and 304 responses have none.
+    /// The response to a HEAD request keeps the headers of the body without sending it
+    async fn write_response<S>(
+        stream: &mut S,
+        mut response: Response,
+        head: bool,
+        timeout: Option<Duration>,
+    ) -> std::io::Result<()>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let body = response.get_body();
+
+        if response.get_header(&Header::Date).is_none() {
+            response.add_header(Header::Date, pillow_http::date::now());
         }
 
-        stream.flush().await?;
-
-        Ok(())
-    }
+        match &body {
+            BodyResponse::STREAM(body) => match body.content_length() {
+                Some(len) => response.add_header(Header::ContentLength, len.to_string()),
+                None => response.add_header(Header::TransferEncoding, "chunked".to_string()),
+            },
+            _ if matches!(
+                response.get_status_code(),
+                status_code::StatusCode::Information(_)
+                    | status_code::StatusCode::Redirection(status_code::Redirection::NotModified)
+            ) => {}
+            body => response.add_header(Header::ContentLength, body.as_bytes().len().to_string()),
+        }
 
-    async fn write_stream_tls(
-        mut stream: TcpStreamTLS,
-        request: &Request,
-        router: &MainRouter,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let vec_response = router.routing(request);
-
-        for response in vec_response {
-            let headers = format!(
-                "{}{}\r\n\r\n",
-                response.get_status_line(),
-                response.get_headers()
-            );
-            let body = response.get_body();
-
-            stream.write_all(headers).await;
-            stream.write_all(body.to_string()).await;
+        if head {
+            with_timeout(timeout, stream.write_all(response.head().as_bytes())).await?;
+            return with_timeout(timeout, stream.flush()).await;
         }
 
-        stream.flush().await;
+        match body {
+            BodyResponse::STREAM(body) => {
+                with_timeout(timeout, stream.write_all(response.head().as_bytes())).await?;
 
-        Ok(())
-    }
+                let chunked = body.content_length().is_none();
+                let mut sent: u64 = 0;
 
-    /// Read data from stream and return a Request
-    fn read_stream(stream: &tokio::net::TcpStream) -> Request {
-        let mut data = vec![0; 1024];
-        // Try to read data, this may still fail with `WouldBlock`
-        // if the readiness event is a false positive.
-        match stream.try_read(&mut data) {
-            Ok(_) => {
-                let parser = Request::from_vec(&data).unwrap();
+                if let Some(mut chunks) = body.take() {
+                    while let Some(chunk) = chunks.next().await {
+                        let chunk = chunk?;
 
-                return parser;
+                        // An empty chunk would end a chunked body
+                        if chunk.is_empty() {
+                            continue;
+                        }
+
+                        sent += chunk.len() as u64;
+
+                        if body.content_length().is_some_and(|len| sent > len) {
+                            return Err(content_length_mismatch());
+                        }
+
+                        let data = match chunked {
+                            true => [format!("{:X}\r\n", chunk.len()).as_bytes(), &chunk, b"\r\n"]
+                                .concat(),
+                            false => chunk,
+                        };
+
+                        // Each chunk is sent now, like the events of Server-Sent Events
+                        with_timeout(timeout, async {
+                            stream.write_all(&data).await?;
+                            stream.flush().await
+                        })
+                        .await?;
+                    }
+                }
+
+                match body.content_length() {
+                    None => with_timeout(timeout, stream.write_all(b"0\r\n\r\n")).await?,
+                    // The client would wait for the missing bytes
+                    Some(len) if sent != len => return Err(content_length_mismatch()),
+                    Some(_) => {}
+                }
             }
-            Err(e) => {
-                panic!("{}", e);
+            // The body is not copied after the head, it can be shared with a cache
+            body => {
+                with_timeout(timeout, async {
+                    stream.write_all(response.head().as_bytes()).await?;
+                    stream.write_all(body.as_bytes()).await
+                })
+                .await?
             }
-        };
-    }
+        }
 
-    async fn read_stream_tls(stream: &mut TcpStreamTLS) -> Request {
-        let data = vec![0; 1024];
-        // Try to read data, this may still fail with `WouldBlock`
-        // if the readiness event is a false positive.
-        let buf = stream.read(data).await.unwrap();
-        Request::from_vec(&buf).unwrap()
+        with_timeout(timeout, stream.flush()).await
     }
 }
 
@@ -425,3 +974,293 @@ impl Listener {
         Ok(())
     }
 }
+
+/// A streamed body without the bytes announced in its Content-Length
+fn content_length_mismatch() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "the body doesn't match its Content-Length",
+    )
+}
+
+/// Await a future, failing with `TimedOut` when the timeout is elapsed
+async fn with_timeout<T, F>(timeout: Option<Duration>, future: F) -> std::io::Result<T>
+where
+    F: std::future::Future<Output = std::io::Result<T>>,
+{
+    match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, future).await {
+            Ok(result) => result,
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "connection timed out",
+            )),
+        },
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn it_handles_any_stream() {
+        let mut router = MainRouter::new();
+        router.add_route_closure(HttpMethods::GET, "/", |request| {
+            Response::text(request.server_name().unwrap_or_default())
+        });
+
+        let info = ConnectionInfo {
+            server_name: Some("example.com".to_string()),
+            hsts: Some(Arc::from("max-age=60")),
+            ..ConnectionInfo::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let connection = tokio::spawn(async move {
+            Listener::handle_connections(
+                server,
+                &router,
+                info,
+                Limits::default(),
+                Timeouts::default(),
+            )
+            .await
+            .unwrap();
+        });
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        connection.await.unwrap();
+
+        assert_eq!(response.matches("HTTP/1.1 200").count(), 2);
+        assert_eq!(
+            response
+                .matches("Strict-Transport-Security: max-age=60")
+                .count(),
+            2
+        );
+        assert!(response.ends_with("example.com"));
+    }
+
+    #[tokio::test]
+    async fn it_sends_no_body_to_head_requests() {
+        let root = std::env::temp_dir().join(format!("pillow-head-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "hello").unwrap();
+
+        let mut router = MainRouter::new();
+        router.serve_dir("/static", &root.to_string_lossy());
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let connection = tokio::spawn(async move {
+            Listener::handle_connections(
+                server,
+                &router,
+                ConnectionInfo::default(),
+                Limits::default(),
+                Timeouts::default(),
+            )
+            .await
+            .unwrap();
+        });
+
+        client
+            .write_all(
+                b"HEAD /static/a.txt HTTP/1.1\r\n\r\nGET /static/a.txt HTTP/1.1\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        connection.await.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let (head, rest) = response.split_once("\r\n\r\n").unwrap();
+
+        assert!(head.contains("Content-Length: 5"));
+        assert!(rest.starts_with("HTTP/1.1 200"));
+        assert!(rest.ends_with("\r\n\r\nhello"));
+    }
+
+    #[tokio::test]
+    async fn it_compresses_the_responses() {
+        let mut router = MainRouter::new();
+        router.add_route_closure(HttpMethods::GET, "/", |_| {
+            Response::text(&"pillow ".repeat(1000))
+        });
+
+        let info = ConnectionInfo {
+            compression: Some(Arc::new(Compression::default())),
+            ..ConnectionInfo::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(16 * 1024);
+        let connection = tokio::spawn(async move {
+            Listener::handle_connections(
+                server,
+                &router,
+                info,
+                Limits::default(),
+                Timeouts::default(),
+            )
+            .await
+            .unwrap();
+        });
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        connection.await.unwrap();
+
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..split]).to_string();
+        let body = &response[split + 4..];
+
+        assert!(head.contains("Content-Encoding: gzip"));
+        assert!(head.contains("Vary: Accept-Encoding"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert!(body.len() < 7000);
+    }
+
+    #[tokio::test]
+    async fn it_streams_server_sent_events() {
+        let mut router = MainRouter::new();
+        router.add_route_closure(HttpMethods::GET, "/events", |request| {
+            let last = request.last_event_id().unwrap_or_default().to_string();
+
+            Response::sse_with_keep_alive(
+                futures_util::stream::iter(vec![
+                    pillow_http::sse::Event::data(last).id("2"),
+                    pillow_http::sse::Event::data("b").event("update"),
+                ]),
+                None,
+            )
+        });
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let connection = tokio::spawn(async move {
+            Listener::handle_connections(
+                server,
+                &router,
+                ConnectionInfo::default(),
+                Limits::default(),
+                Timeouts::default(),
+            )
+            .await
+            .unwrap();
+        });
+
+        client
+            .write_all(b"GET /events HTTP/1.1\r\nLast-Event-ID: 1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        connection.await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+
+        assert!(head.contains("Content-Type: text/event-stream"));
+        assert!(head.contains("Transfer-Encoding: chunked"));
+        assert!(!head.contains("Content-Length"));
+        assert_eq!(
+            body,
+            "F\r\nid: 2\ndata: 1\n\n\r\n17\r\nevent: update\ndata: b\n\n\r\n0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_checks_the_length_of_streamed_bodies() {
+        let body = |chunks: Vec<&'static [u8]>| {
+            futures_util::stream::iter(chunks.into_iter().map(|chunk| Ok(chunk.to_vec())))
+        };
+
+        let (mut client, mut server) = tokio::io::duplex(4096);
+
+        let response = Response::stream_with_len(body(vec![b"hello ", b"world"]), 11);
+        Listener::write_response(&mut server, response, false, None)
+            .await
+            .unwrap();
+
+        let response = Response::stream_with_len(body(vec![b"hello"]), 11);
+        assert!(Listener::write_response(&mut server, response, false, None)
+            .await
+            .is_err());
+        drop(server);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+
+        assert!(head.contains("Content-Length: 11"));
+        assert!(!head.contains("Transfer-Encoding"));
+        assert!(body.starts_with("hello worldHTTP/1.1 200"));
+
+        let date = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Date: "))
+            .unwrap();
+        assert!(pillow_http::date::parse_http_date(date).is_some());
+    }
+
+    #[tokio::test]
+    async fn it_upgrades_websocket_routes() {
+        let mut router = MainRouter::new();
+        router
+            .websocket("/ws", |mut ws: WebSocket| async move {
+                if let Ok(Some(message)) = ws.recv().await {
+                    ws.send(message).await.unwrap();
+                }
+            })
+            .protocols(&["chat"]);
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let connection = tokio::spawn(async move {
+            Listener::handle_connections(
+                server,
+                &router,
+                ConnectionInfo::default(),
+                Limits::default(),
+                Timeouts::default(),
+            )
+            .await
+            .unwrap();
+        });
+
+        // Masked text frame "hi" sent with the upgrade request
+        let mut data = b"GET /ws HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\n\
+            Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\nSec-WebSocket-Protocol: superchat, chat\r\n\r\n"
+            .to_vec();
+        data.extend_from_slice(&[0x81, 0x82, 1, 2, 3, 4, b'h' ^ 1, b'i' ^ 2]);
+        client.write_all(&data).await.unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        connection.await.unwrap();
+
+        let head_len = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let (head, frame) = response.split_at(head_len);
+        let head = String::from_utf8_lossy(head);
+
+        assert!(head.starts_with("HTTP/1.1 101"));
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        assert!(head.contains("Sec-WebSocket-Protocol: chat"));
+        assert!(!head.contains("Content-Length"));
+        assert_eq!(frame, [0x81, 2, b'h', b'i']);
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters of the Server, shared by all the listeners
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use pillow_server::Server;
+///
+/// #[tokio::main]
+/// async fn main(){
+///     let server = Server::builder().port(0).build().unwrap();
+///     let stats = server.stats();
+///
+///     println!("{}", stats.tls_handshake_failures());
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    tls_handshake_failures: AtomicU64,
+}
+
+impl ServerStats {
+    /// TLS handshakes failed or timed out
+    pub fn tls_handshake_failures(&self) -> u64 {
+        self.tls_handshake_failures.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn tls_handshake_failed(&self) {
+        self.tls_handshake_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
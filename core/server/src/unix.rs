@@ -0,0 +1,88 @@
+use std::{
+    fs, io,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use tokio::net::UnixListener;
+
+use crate::builder::UnixSocket;
+
+/// Path of a bound Unix domain socket, the file is removed on drop
+#[derive(Debug)]
+pub(crate) struct UnixSocketGuard {
+    path: PathBuf,
+}
+
+impl UnixSocketGuard {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnixSocketGuard {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                eprintln!("Could not remove {}: {}", self.path.display(), err);
+            }
+        }
+    }
+}
+
+/// Bind a Unix domain socket
+///
+/// A stale socket, left by a server that is not running, is removed first
+/// when `remove_stale` is enabled.
+pub(crate) fn bind(config: &UnixSocket) -> io::Result<(UnixListener, UnixSocketGuard)> {
+    let path = &config.path;
+
+    if config.remove_stale {
+        remove_stale(path)?;
+    }
+
+    let listener = UnixListener::bind(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Could not bind {}: {}", path.display(), err),
+        )
+    })?;
+
+    let guard = UnixSocketGuard { path: path.clone() };
+
+    if let Some(mode) = config.mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+
+    Ok((listener, guard))
+}
+
+/// Remove the socket file when no server is accepting on it
+fn remove_stale(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another server", path.display()),
+        )),
+
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+            println!("Removing stale socket {}", path.display());
+            fs::remove_file(path)
+        }
+
+        Err(err) => Err(err),
+    }
+}
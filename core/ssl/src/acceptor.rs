@@ -0,0 +1,268 @@
+use std::{
+    fs, io,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use pillow_config::{get_config, Ssl};
+use tokio::{net::TcpStream as TokioTcpStream, task::JoinHandle};
+
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+use crate::native::Acceptor;
+#[cfg(feature = "rustls")]
+use crate::rustls_tls::Acceptor;
+use crate::TcpStream;
+
+/// Acceptor of TLS connections
+///
+/// The certificates loaded from a ssl configuration can be reloaded while
+/// the server runs, new connections use the new certificates and the open
+/// connections keep the old ones. Clones share the certificates.
+#[derive(Debug, Clone)]
+pub struct TlsAcceptor {
+    current: Arc<RwLock<Arc<Acceptor>>>,
+
+    /// Configuration to reload, `None` for certificates loaded from memory
+    config: Option<Arc<Ssl>>,
+}
+
+impl TlsAcceptor {
+    /// Instance of TlsAcceptor from `[server.ssl]` in pillow.toml
+    pub fn new() -> TlsAcceptor {
+        let ssl_config = get_config()
+            .server()
+            .ssl()
+            .expect("Add [server.ssl] in pillow.toml");
+
+        Self::from_config(&ssl_config).expect("Failed to load TLS identity")
+    }
+
+    /// Instance of TlsAcceptor from a ssl configuration
+    ///
+    /// With native-tls, `ciphers`, `alpn` and `client_auth` are not supported
+    ///
+    /// # Arguments
+    ///
+    /// * ssl_config - paths of the certificate chain and the key,
+    ///   min version, cipher suites, ALPN protocols, the certificates
+    ///   by SNI server name and the client certificates policy
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use pillow_config::Ssl;
+    /// use pillow_ssl::TlsAcceptor;
+    ///
+    /// let acceptor = TlsAcceptor::from_config(&Ssl {
+    ///     cert: "fullchain.pem".to_string(),
+    ///     key: "privkey.pem".to_string(),
+    ///     min_version: Some("1.2".to_string()),
+    ///     ..Ssl::default()
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn from_config(ssl_config: &Ssl) -> io::Result<TlsAcceptor> {
+        Ok(Self {
+            current: Self::shared(Acceptor::from_config(ssl_config)?),
+            config: Some(Arc::new(ssl_config.clone())),
+        })
+    }
+
+    /// Instance of TlsAcceptor from a PEM certificate and a PEM PKCS #8 key
+    pub fn from_pkcs8(cert: &[u8], key: &[u8]) -> io::Result<TlsAcceptor> {
+        Ok(Self::from_acceptor(Acceptor::from_pkcs8(cert, key)?))
+    }
+
+    /// Instance of TlsAcceptor from a PEM certificate chain and a PEM key
+    ///
+    /// The key can be PKCS #8, RSA (PKCS #1) or EC (SEC1)
+    #[cfg(feature = "rustls")]
+    pub fn from_pem(cert: &[u8], key: &[u8]) -> io::Result<TlsAcceptor> {
+        Ok(Self::from_acceptor(Acceptor::from_pem(cert, key)?))
+    }
+
+    /// Instance of TlsAcceptor from a rustls configuration
+    #[cfg(feature = "rustls")]
+    pub fn from_server_config(config: rustls::ServerConfig) -> TlsAcceptor {
+        Self::from_acceptor(Acceptor::from_server_config(config))
+    }
+
+    fn from_acceptor(acceptor: Acceptor) -> Self {
+        Self {
+            current: Self::shared(acceptor),
+            config: None,
+        }
+    }
+
+    fn shared(acceptor: Acceptor) -> Arc<RwLock<Arc<Acceptor>>> {
+        Arc::new(RwLock::new(Arc::new(acceptor)))
+    }
+
+    /// TLS handshake with the current certificates
+    pub async fn accept(
+        &self,
+        stream: TokioTcpStream,
+    ) -> Result<TcpStream, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let acceptor = self.current.read().unwrap().clone();
+
+        acceptor.accept(stream).await
+    }
+}
+
+impl TlsAcceptor {
+    /// Load again the files of the ssl configuration
+    ///
+    /// On error the current certificates are kept
+    pub fn reload(&self) -> io::Result<()> {
+        let config = self.config.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Only a TlsAcceptor created from a ssl configuration can be reloaded",
+            )
+        })?;
+
+        let acceptor = Acceptor::from_config(config)?;
+        *self.current.write().unwrap() = Arc::new(acceptor);
+
+        Ok(())
+    }
+
+    /// Reload and log the result
+    fn reload_and_log(&self) {
+        match self.reload() {
+            Ok(()) => println!("TLS certificates reloaded"),
+            Err(err) => {
+                eprintln!("Could not reload the TLS certificates, keeping the old ones: {err}")
+            }
+        }
+    }
+
+    /// Start the reloads of `watch` and `reload_on_sighup` in `[server.ssl]`
+    ///
+    /// Abort the returned tasks to stop them
+    pub fn start_reload(&self) -> io::Result<Vec<JoinHandle<()>>> {
+        let mut tasks = Vec::new();
+
+        let Some(config) = &self.config else {
+            return Ok(tasks);
+        };
+
+        if let Some(interval) = config.watch {
+            tasks.push(self.watch(Duration::from_millis(interval)));
+        }
+
+        if config.reload_on_sighup.unwrap_or(false) {
+            tasks.push(self.reload_on_sighup()?);
+        }
+
+        Ok(tasks)
+    }
+
+    /// Reload when the certificate files change
+    ///
+    /// # Arguments
+    ///
+    /// * interval - Time between two checks of the files
+    pub fn watch(&self, interval: Duration) -> JoinHandle<()> {
+        let acceptor = self.clone();
+
+        tokio::spawn(async move {
+            let mut last_modified = acceptor.modified();
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let modified = acceptor.modified();
+
+                if modified != last_modified {
+                    last_modified = modified;
+                    acceptor.reload_and_log();
+                }
+            }
+        })
+    }
+
+    /// Reload when the process gets SIGHUP
+    #[cfg(unix)]
+    pub fn reload_on_sighup(&self) -> io::Result<JoinHandle<()>> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = signal(SignalKind::hangup())?;
+        let acceptor = self.clone();
+
+        Ok(tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                acceptor.reload_and_log();
+            }
+        }))
+    }
+
+    /// SIGHUP only exists on Unix
+    #[cfg(not(unix))]
+    pub fn reload_on_sighup(&self) -> io::Result<JoinHandle<()>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SIGHUP is not supported on this platform",
+        ))
+    }
+
+    /// Modification times of the files of the ssl configuration
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        let Some(config) = &self.config else {
+            return Vec::new();
+        };
+
+        let mut paths = vec![&config.cert, &config.key];
+        paths.extend(&config.client_ca);
+
+        for certificate in config.certificates.iter().flatten() {
+            paths.push(&certificate.cert);
+            paths.push(&certificate.key);
+        }
+
+        paths
+            .into_iter()
+            .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_old_certificates_when_the_reload_fails() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pillow-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        fs::write(path("cert.pem"), cert.pem()).unwrap();
+        fs::write(path("key.pem"), key.serialize_pem()).unwrap();
+
+        let acceptor = TlsAcceptor::from_config(&Ssl {
+            cert: path("cert.pem"),
+            key: path("key.pem"),
+            ..Ssl::default()
+        })
+        .unwrap();
+
+        let before = acceptor.current.read().unwrap().clone();
+        acceptor.reload().unwrap();
+        assert!(!Arc::ptr_eq(&before, &acceptor.current.read().unwrap()));
+
+        let before = acceptor.current.read().unwrap().clone();
+        fs::write(path("cert.pem"), "not a certificate").unwrap();
+        assert!(acceptor.reload().is_err());
+        assert!(Arc::ptr_eq(&before, &acceptor.current.read().unwrap()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
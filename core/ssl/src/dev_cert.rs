@@ -0,0 +1,103 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+
+use crate::invalid_data;
+
+/// Names of the self-signed certificate
+const DEV_NAMES: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+/// Self-signed certificate for localhost, 127.0.0.1 and ::1
+///
+/// The certificate is generated the first time and reused after.
+/// Returns the paths of the PEM certificate and the PEM PKCS #8 key.
+///
+/// Browsers warn about it, it's only for development
+///
+/// # Arguments
+///
+/// * dir - Directory of `cert.pem` and `key.pem`
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let (cert, key) = pillow_ssl::dev_certificate("target/pillow/dev-cert").unwrap();
+/// ```
+pub fn dev_certificate<P: AsRef<Path>>(dir: P) -> io::Result<(PathBuf, PathBuf)> {
+    let dir = dir.as_ref();
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+
+    if cert_path.is_file() && key_path.is_file() {
+        return Ok((cert_path, key_path));
+    }
+
+    let key = KeyPair::generate().map_err(invalid_data)?;
+
+    let mut params =
+        CertificateParams::new(DEV_NAMES.map(String::from).to_vec()).map_err(invalid_data)?;
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, "Pillow development certificate");
+    params.distinguished_name = name;
+
+    let cert = params.self_signed(&key).map_err(invalid_data)?;
+
+    fs::create_dir_all(dir)?;
+    write_private(&key_path, key.serialize_pem())?;
+    fs::write(&cert_path, cert.pem())?;
+
+    println!(
+        "Generated a self-signed development certificate in {}",
+        dir.display()
+    );
+
+    Ok((cert_path, key_path))
+}
+
+/// Write the key readable only by the owner
+#[cfg(unix)]
+fn write_private(path: &Path, contents: String) -> io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: String) -> io::Result<()> {
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TlsAcceptor;
+
+    #[test]
+    fn it_generates_the_certificate_once() {
+        let dir = std::env::temp_dir().join(format!("pillow-dev-cert-{}", std::process::id()));
+
+        let (cert, key) = dev_certificate(&dir).unwrap();
+        let pem = fs::read(&cert).unwrap();
+
+        assert_eq!(dev_certificate(&dir).unwrap(), (cert.clone(), key.clone()));
+        assert_eq!(fs::read(&cert).unwrap(), pem);
+
+        let (_, der) = x509_parser::pem::parse_x509_pem(&pem).unwrap();
+        let cert = der.parse_x509().unwrap();
+        let sans = cert.subject_alternative_name().unwrap().unwrap();
+        assert_eq!(sans.value.general_names.len(), 3);
+
+        assert!(TlsAcceptor::from_pkcs8(&pem, &fs::read(&key).unwrap()).is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
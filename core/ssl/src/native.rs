@@ -0,0 +1,518 @@
+use std::{
+    io,
+    ops::Deref,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use native_tls::{Identity, Protocol, TlsAcceptor as NativeTlsAcceptor};
+use pillow_config::{ClientAuth, Ssl};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf},
+    net::TcpStream as TokioTcpStream,
+};
+use tokio_native_tls::{TlsAcceptor as TokioTlsAcceptor, TlsStream};
+
+use crate::{invalid_data, read_file, sni::SniMap};
+
+/// Max size of a TLS record
+const MAX_RECORD: usize = 5 + 16 * 1024;
+
+/// Longest wait of the ClientHello, even without handshake timeout
+const CLIENT_HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// native-tls acceptors of the certificates
+#[derive(Debug, Clone)]
+pub(crate) struct Acceptor {
+    tls: TokioTlsAcceptor,
+
+    /// Acceptors of the `[[server.ssl.certificates]]`
+    sni: SniMap<TokioTlsAcceptor>,
+}
+
+impl Acceptor {
+    /// `ciphers`, `alpn` and `client_auth` need the rustls feature
+    pub(crate) fn from_config(ssl_config: &Ssl) -> io::Result<Self> {
+        if ssl_config.ciphers.is_some()
+            || ssl_config.alpn.is_some()
+            || ssl_config.client_auth() != ClientAuth::None
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "ciphers, alpn and client_auth need the rustls feature of pillow-ssl",
+            ));
+        }
+
+        let cert = read_file(&ssl_config.cert, "certificate")?;
+        let key = read_file(&ssl_config.key, "key")?;
+
+        let min_version = match ssl_config.min_version.as_deref() {
+            None | Some("1.2") => Protocol::Tlsv12,
+            Some(version) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unsupported TLS version {version}, native-tls supports 1.2"),
+                ))
+            }
+        };
+
+        let mut acceptor = Self::build(&cert, &key, min_version)?;
+
+        if let Some(certificates) = &ssl_config.certificates {
+            acceptor.sni = SniMap::load(certificates, |cert, key| {
+                Ok(Self::build(cert, key, min_version)?.tls)
+            })?;
+        }
+
+        Ok(acceptor)
+    }
+
+    pub(crate) fn from_pkcs8(cert: &[u8], key: &[u8]) -> io::Result<Self> {
+        Self::build(cert, key, Protocol::Tlsv12)
+    }
+
+    fn build(cert: &[u8], key: &[u8], min_version: Protocol) -> io::Result<Self> {
+        let identity = Identity::from_pkcs8(cert, key).map_err(invalid_data)?;
+        let acceptor = NativeTlsAcceptor::builder(identity)
+            .min_protocol_version(Some(min_version))
+            .build()
+            .map_err(invalid_data)?;
+
+        Ok(Self {
+            tls: TokioTlsAcceptor::from(acceptor),
+            sni: SniMap::default(),
+        })
+    }
+
+    /// TLS handshake, with the certificate of the SNI server name
+    pub(crate) async fn accept(
+        &self,
+        stream: TokioTcpStream,
+    ) -> Result<TcpStream, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut stream = stream;
+
+        // native-tls doesn't expose SNI, the ClientHello is read and replayed to the handshake
+        let (hello, server_name) =
+            tokio::time::timeout(CLIENT_HELLO_TIMEOUT, read_client_hello(&mut stream))
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, "no ClientHello received")
+                })??;
+
+        let tls = server_name
+            .as_deref()
+            .and_then(|name| self.sni.get(name))
+            .unwrap_or(&self.tls);
+
+        let tls_stream = tls.accept(ReplayStream::new(hello, stream)).await?;
+
+        let mut s = TcpStream::new(tls_stream);
+        s.server_name = server_name;
+
+        Ok(s)
+    }
+}
+
+/// Bytes read until the end of the ClientHello, with its SNI server name
+///
+/// The wait for the rest of a record is a read, not a new poll of the stream
+async fn read_client_hello<S>(stream: &mut S) -> io::Result<(Vec<u8>, Option<String>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut hello = Vec::new();
+    let mut chunk = [0; 4096];
+
+    loop {
+        if let Some(server_name) = client_hello_server_name(&hello) {
+            return Ok((hello, server_name));
+        }
+
+        // Bigger than a record, the handshake fails with it
+        if hello.len() >= MAX_RECORD {
+            return Ok((hello, None));
+        }
+
+        let size = stream.read(&mut chunk).await?;
+
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before the ClientHello",
+            ));
+        }
+
+        hello.extend_from_slice(&chunk[..size]);
+    }
+}
+
+/// SNI server name of a ClientHello record
+///
+/// `None` when the record is incomplete, `Some(None)` without server name
+/// or when the data is not a ClientHello
+fn client_hello_server_name(data: &[u8]) -> Option<Option<String>> {
+    // TLS record: type, version, length
+    if data.len() < 5 {
+        return None;
+    }
+
+    if data[0] != 0x16 {
+        return Some(None);
+    }
+
+    let record_len = u16::from_be_bytes([data[3], data[4]]) as usize;
+
+    if data.len() < 5 + record_len {
+        return None;
+    }
+
+    Some(parse_client_hello(&data[5..5 + record_len]))
+}
+
+fn parse_client_hello(mut data: &[u8]) -> Option<String> {
+    // Handshake type ClientHello and length
+    if take(&mut data, 4)?[0] != 0x01 {
+        return None;
+    }
+
+    // Version and random
+    take(&mut data, 2 + 32)?;
+
+    // Session id, cipher suites and compression methods
+    let len = take(&mut data, 1)?[0] as usize;
+    take(&mut data, len)?;
+    let len = take_u16(&mut data)?;
+    take(&mut data, len)?;
+    let len = take(&mut data, 1)?[0] as usize;
+    take(&mut data, len)?;
+
+    let len = take_u16(&mut data)?;
+    let mut extensions = take(&mut data, len)?;
+
+    while !extensions.is_empty() {
+        let kind = take_u16(&mut extensions)?;
+        let len = take_u16(&mut extensions)?;
+        let mut extension = take(&mut extensions, len)?;
+
+        // server_name extension with a host_name entry
+        if kind == 0 {
+            take_u16(&mut extension)?;
+
+            if take(&mut extension, 1)?[0] != 0 {
+                return None;
+            }
+
+            let len = take_u16(&mut extension)?;
+            let name = take(&mut extension, len)?;
+
+            return String::from_utf8(name.to_vec()).ok();
+        }
+    }
+
+    None
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+
+    Some(head)
+}
+
+fn take_u16(data: &mut &[u8]) -> Option<usize> {
+    let bytes = take(data, 2)?;
+
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+/// TCP connection that reads again the bytes of the ClientHello read for SNI
+#[derive(Debug)]
+pub struct ReplayStream {
+    read: Vec<u8>,
+    position: usize,
+    stream: TokioTcpStream,
+}
+
+impl ReplayStream {
+    fn new(read: Vec<u8>, stream: TokioTcpStream) -> Self {
+        Self {
+            read,
+            position: 0,
+            stream,
+        }
+    }
+
+    pub fn get_ref(&self) -> &TokioTcpStream {
+        &self.stream
+    }
+}
+
+impl AsyncRead for ReplayStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.position < this.read.len() {
+            let len = buf.remaining().min(this.read.len() - this.position);
+
+            buf.put_slice(&this.read[this.position..this.position + len]);
+            this.position += len;
+
+            if this.position == this.read.len() {
+                this.read = Vec::new();
+                this.position = 0;
+            }
+
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut this.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ReplayStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// TLS connection, read and written with the tokio io traits
+pub struct TcpStream {
+    stream: TlsStream<ReplayStream>,
+    server_name: Option<String>,
+}
+
+impl Deref for TcpStream {
+    type Target = TlsStream<ReplayStream>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl TcpStream {
+    pub fn new(stream: TlsStream<ReplayStream>) -> Self {
+        Self {
+            stream,
+            server_name: None,
+        }
+    }
+
+    /// Server name sent by the client with SNI
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// DER of the client certificate, native-tls doesn't ask for it
+    pub fn peer_certificate(&self) -> Option<Vec<u8>> {
+        let cert = self.stream.get_ref().peer_certificate().ok()??;
+
+        cert.to_der().ok()
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_hello(name: &[u8]) -> Vec<u8> {
+        let mut hello = vec![0x03, 0x03];
+        hello.extend_from_slice(&[0; 32]);
+        hello.extend_from_slice(&[0, 0, 2, 0x13, 0x01, 1, 0]);
+
+        let mut sni = vec![0, 0];
+        sni.extend_from_slice(&((name.len() + 5) as u16).to_be_bytes());
+        sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+        sni.push(0);
+        sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        sni.extend_from_slice(name);
+
+        hello.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+        hello.extend_from_slice(&sni);
+
+        let mut handshake = vec![0x01, 0];
+        handshake.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&hello);
+
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+
+        record
+    }
+
+    #[test]
+    fn it_reads_the_server_name_of_a_client_hello() {
+        let record = client_hello(b"example.com");
+
+        assert_eq!(
+            client_hello_server_name(&record),
+            Some(Some("example.com".to_string()))
+        );
+        assert_eq!(client_hello_server_name(&record[..20]), None);
+        assert_eq!(client_hello_server_name(b"GET / HTTP/1.1"), Some(None));
+    }
+
+    #[tokio::test]
+    async fn it_reads_the_client_hello_until_its_end() {
+        let record = client_hello(b"example.com");
+        let mut stream = (&record[..20]).chain(&record[20..]);
+
+        let (hello, server_name) = read_client_hello(&mut stream).await.unwrap();
+
+        assert_eq!(hello, record);
+        assert_eq!(server_name.as_deref(), Some("example.com"));
+
+        let err = read_client_hello(&mut &record[..20]).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn it_replays_the_client_hello_to_the_certificate_of_the_server_name() {
+        let dir = std::env::temp_dir().join(format!("pillow-native-sni-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        let mut ders = Vec::new();
+
+        for (name, hostname) in [("default", "localhost"), ("sni", "example.com")] {
+            let key = rcgen::KeyPair::generate().unwrap();
+            let cert = rcgen::CertificateParams::new(vec![hostname.to_string()])
+                .unwrap()
+                .self_signed(&key)
+                .unwrap();
+
+            std::fs::write(path(&format!("{name}.pem")), cert.pem()).unwrap();
+            std::fs::write(path(&format!("{name}.key")), key.serialize_pem()).unwrap();
+            ders.push(cert.der().to_vec());
+        }
+
+        let acceptor = Acceptor::from_config(&Ssl {
+            cert: path("default.pem"),
+            key: path("default.key"),
+            certificates: Some(vec![pillow_config::SslCertificate {
+                hostnames: vec!["example.com".to_string()],
+                cert: path("sni.pem"),
+                key: path("sni.key"),
+            }]),
+            ..Ssl::default()
+        })
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+
+            let mut hello = [0; 5];
+            stream.read_exact(&mut hello).await.unwrap();
+
+            (stream.server_name().map(str::to_string), hello)
+        });
+
+        let connector = tokio_native_tls::TlsConnector::from(
+            native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .build()
+                .unwrap(),
+        );
+        let tcp = TokioTcpStream::connect(addr).await.unwrap();
+        let mut client = connector.connect("example.com", tcp).await.unwrap();
+
+        let cert = client.get_ref().peer_certificate().unwrap().unwrap();
+        assert_eq!(cert.to_der().unwrap(), ders[1]);
+
+        tokio::io::AsyncWriteExt::write_all(&mut client, b"hello")
+            .await
+            .unwrap();
+
+        let (server_name, hello) = server.await.unwrap();
+        assert_eq!(server_name.as_deref(), Some("example.com"));
+        assert_eq!(&hello, b"hello");
+    }
+
+    #[tokio::test]
+    async fn it_exposes_the_server_name_without_sni_certificates() {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .self_signed(&key)
+            .unwrap();
+        let acceptor =
+            Acceptor::from_pkcs8(cert.pem().as_bytes(), key.serialize_pem().as_bytes()).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let stream = acceptor.accept(stream).await.unwrap();
+
+            stream.server_name().map(str::to_string)
+        });
+
+        let connector = tokio_native_tls::TlsConnector::from(
+            native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .build()
+                .unwrap(),
+        );
+        let tcp = TokioTcpStream::connect(addr).await.unwrap();
+        let client = connector.connect("example.com", tcp).await.unwrap();
+
+        let peer = client.get_ref().peer_certificate().unwrap().unwrap();
+        assert_eq!(peer.to_der().unwrap(), cert.der().to_vec());
+        assert_eq!(server.await.unwrap().as_deref(), Some("example.com"));
+    }
+}
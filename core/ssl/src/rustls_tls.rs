@@ -0,0 +1,406 @@
+use std::{
+    io,
+    ops::Deref,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use pillow_config::{ClientAuth, Ssl};
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{danger::ClientCertVerifier, ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    version, RootCertStore, ServerConfig, SupportedCipherSuite, SupportedProtocolVersion,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream as TokioTcpStream,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor as TokioTlsAcceptor};
+
+use crate::{invalid_data, read_file, sni::SniMap};
+
+/// ALPN protocols advertised without `alpn` in `[server.ssl]`
+const DEFAULT_ALPN: &[&str] = &["http/1.1"];
+
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&version::TLS13];
+
+/// rustls acceptor of the certificates
+#[derive(Clone)]
+pub(crate) struct Acceptor {
+    tls: TokioTlsAcceptor,
+}
+
+impl std::fmt::Debug for Acceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Acceptor").finish_non_exhaustive()
+    }
+}
+
+impl Acceptor {
+    pub(crate) fn from_config(ssl_config: &Ssl) -> io::Result<Self> {
+        let cert = read_file(&ssl_config.cert, "certificate")?;
+        let key = read_file(&ssl_config.key, "key")?;
+
+        Ok(Self::from_server_config(server_config(
+            &cert, &key, ssl_config,
+        )?))
+    }
+
+    /// The key can be PKCS #8, RSA (PKCS #1) or EC (SEC1)
+    pub(crate) fn from_pem(cert: &[u8], key: &[u8]) -> io::Result<Self> {
+        Ok(Self::from_server_config(server_config(
+            cert,
+            key,
+            &Ssl::default(),
+        )?))
+    }
+
+    pub(crate) fn from_pkcs8(cert: &[u8], key: &[u8]) -> io::Result<Self> {
+        Self::from_pem(cert, key)
+    }
+
+    pub(crate) fn from_server_config(config: ServerConfig) -> Self {
+        Self {
+            tls: TokioTlsAcceptor::from(Arc::new(config)),
+        }
+    }
+
+    pub(crate) async fn accept(
+        &self,
+        stream: TokioTcpStream,
+    ) -> Result<TcpStream, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let tls_stream = self.tls.accept(stream).await?;
+
+        let s = TcpStream::new(tls_stream);
+
+        Ok(s)
+    }
+}
+
+/// Certificate chosen by the SNI server name
+#[derive(Debug)]
+struct SniResolver {
+    default: Arc<CertifiedKey>,
+    names: SniMap<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let certified_key = client_hello
+            .server_name()
+            .and_then(|name| self.names.get(name))
+            .unwrap_or(&self.default);
+
+        Some(certified_key.clone())
+    }
+}
+
+/// rustls configuration of the certificate, the key and `[server.ssl]`
+fn server_config(cert: &[u8], key: &[u8], ssl_config: &Ssl) -> io::Result<ServerConfig> {
+    let mut provider = ring::default_provider();
+
+    let resolver = SniResolver {
+        default: certified_key(&provider, cert, key)?,
+        names: SniMap::load(
+            ssl_config.certificates.as_deref().unwrap_or_default(),
+            |cert, key| certified_key(&provider, cert, key),
+        )?,
+    };
+
+    if let Some(ciphers) = &ssl_config.ciphers {
+        provider.cipher_suites = ciphers
+            .iter()
+            .map(|name| cipher_suite(name))
+            .collect::<io::Result<_>>()?;
+    }
+
+    let provider = Arc::new(provider);
+
+    let mut config = ServerConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(protocol_versions(ssl_config.min_version.as_deref())?)
+        .map_err(invalid_data)?
+        .with_client_cert_verifier(client_verifier(ssl_config, provider)?)
+        .with_cert_resolver(Arc::new(resolver));
+
+    config.alpn_protocols = match &ssl_config.alpn {
+        Some(alpn) => alpn.iter().map(|p| p.as_bytes().to_vec()).collect(),
+        None => DEFAULT_ALPN.iter().map(|p| p.as_bytes().to_vec()).collect(),
+    };
+
+    Ok(config)
+}
+
+/// Verifier of the client certificates with the CAs of `client_ca`
+fn client_verifier(
+    ssl_config: &Ssl,
+    provider: Arc<CryptoProvider>,
+) -> io::Result<Arc<dyn ClientCertVerifier>> {
+    let client_auth = ssl_config.client_auth();
+
+    if client_auth == ClientAuth::None {
+        return Ok(WebPkiClientVerifier::no_client_auth());
+    }
+
+    let client_ca = ssl_config.client_ca.as_ref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "client_auth needs client_ca in [server.ssl]",
+        )
+    })?;
+
+    let mut roots = RootCertStore::empty();
+
+    for cert in load_certs(&read_file(client_ca, "client CA")?)? {
+        roots.add(cert).map_err(invalid_data)?;
+    }
+
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+
+    let builder = match client_auth {
+        ClientAuth::Optional => builder.allow_unauthenticated(),
+        _ => builder,
+    };
+
+    builder.build().map_err(invalid_data)
+}
+
+/// Certificate chain and signing key, the key must match the certificate
+fn certified_key(
+    provider: &CryptoProvider,
+    cert: &[u8],
+    key: &[u8],
+) -> io::Result<Arc<CertifiedKey>> {
+    let certified_key = CertifiedKey::from_der(load_certs(cert)?, load_key(key)?, provider)
+        .map_err(invalid_data)?;
+
+    Ok(Arc::new(certified_key))
+}
+
+/// Certificates of a PEM chain, leaf first
+fn load_certs(pem: &[u8]) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut &pem[..]).collect::<io::Result<Vec<_>>>()?;
+
+    if certs.is_empty() {
+        return Err(invalid_data("No certificate found in the PEM file"));
+    }
+
+    Ok(certs)
+}
+
+/// First PKCS #8, RSA or EC private key of a PEM file
+fn load_key(pem: &[u8]) -> io::Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut &pem[..])?
+        .ok_or_else(|| invalid_data("No private key found in the PEM file"))
+}
+
+/// TLS versions from the minimum version, "1.2" or "1.3"
+fn protocol_versions(
+    min_version: Option<&str>,
+) -> io::Result<&'static [&'static SupportedProtocolVersion]> {
+    match min_version {
+        None | Some("1.2") => Ok(rustls::ALL_VERSIONS),
+        Some("1.3") => Ok(TLS13_ONLY),
+        Some(version) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported TLS version {version}, use 1.2 or 1.3"),
+        )),
+    }
+}
+
+/// Cipher suite by its name, like `TLS13_AES_256_GCM_SHA384`
+fn cipher_suite(name: &str) -> io::Result<SupportedCipherSuite> {
+    let provider: CryptoProvider = ring::default_provider();
+
+    provider
+        .cipher_suites
+        .into_iter()
+        .find(|suite| format!("{:?}", suite.suite()).eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown cipher suite {name}"),
+            )
+        })
+}
+
+/// TLS connection, read and written with the tokio io traits
+pub struct TcpStream {
+    stream: TlsStream<TokioTcpStream>,
+}
+
+impl Deref for TcpStream {
+    type Target = TlsStream<TokioTcpStream>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl TcpStream {
+    pub fn new(stream: TlsStream<TokioTcpStream>) -> Self {
+        Self { stream }
+    }
+
+    /// Server name sent by the client with SNI
+    pub fn server_name(&self) -> Option<&str> {
+        self.stream.get_ref().1.server_name()
+    }
+
+    /// DER of the client certificate verified during the handshake
+    pub fn peer_certificate(&self) -> Option<Vec<u8>> {
+        let certs = self.stream.get_ref().1.peer_certificates()?;
+
+        Some(certs.first()?.to_vec())
+    }
+
+    /// Protocol chosen with ALPN
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.stream.get_ref().1.alpn_protocol()
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_cipher_suites_by_name() {
+        assert!(cipher_suite("TLS13_AES_256_GCM_SHA384").is_ok());
+        assert!(cipher_suite("TLS_NOT_A_SUITE").is_err());
+    }
+
+    #[test]
+    fn it_loads_the_example_certificate() {
+        let cert = include_bytes!("../../../examples/ssl/cert/cert.pem");
+        let key = include_bytes!("../../../examples/ssl/cert/key.pem");
+
+        assert!(Acceptor::from_pem(cert, key).is_ok());
+        assert!(protocol_versions(Some("1.1")).is_err());
+    }
+
+    #[tokio::test]
+    async fn it_verifies_client_certificates() {
+        use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+        use rustls::{pki_types::ServerName, ClientConfig};
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let server = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params =
+            CertificateParams::new(vec!["billing.internal".to_string()]).unwrap();
+        client_params
+            .distinguished_name
+            .push(DnType::CommonName, "billing");
+        let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("pillow-mtls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        std::fs::write(path("ca.pem"), ca.pem()).unwrap();
+        std::fs::write(path("cert.pem"), server.pem()).unwrap();
+        std::fs::write(path("key.pem"), server_key.serialize_pem()).unwrap();
+
+        let acceptor = Acceptor::from_config(&Ssl {
+            cert: path("cert.pem"),
+            key: path("key.pem"),
+            client_ca: Some(path("ca.pem")),
+            ..Ssl::default()
+        })
+        .unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(
+                vec![client.der().clone()],
+                PrivateKeyDer::try_from(client_key.serialize_der()).unwrap(),
+            )
+            .unwrap();
+
+        let connect = tokio::spawn(async move {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+            let mut stream = connector
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await
+                .unwrap();
+            tokio::io::AsyncWriteExt::write_all(&mut stream, b"ping")
+                .await
+                .unwrap();
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(stream).await.unwrap();
+        connect.await.unwrap();
+
+        assert_eq!(stream.peer_certificate().unwrap(), client.der().to_vec());
+
+        // Without a client certificate the handshake fails
+        let connect = tokio::spawn(async move {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let mut roots = RootCertStore::empty();
+            roots.add(ca.der().clone()).unwrap();
+            let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+            let _ = connector
+                .connect(ServerName::try_from("localhost").unwrap(), stream)
+                .await;
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(acceptor.accept(stream).await.is_err());
+        connect.await.unwrap();
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
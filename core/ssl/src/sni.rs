@@ -0,0 +1,81 @@
+use std::{collections::HashMap, io};
+
+use pillow_config::SslCertificate;
+
+use crate::read_file;
+
+/// Values chosen by the SNI server name
+///
+/// Names are exact, like `example.com`, or wildcards of one label,
+/// like `*.example.com`
+#[derive(Debug, Clone)]
+pub(crate) struct SniMap<T> {
+    names: HashMap<String, T>,
+}
+
+impl<T: Clone> SniMap<T> {
+    /// Load the certificates of `[[server.ssl.certificates]]`
+    ///
+    /// # Arguments
+    ///
+    /// * certificates - Certificates with their hostnames
+    /// * load - Create the value from the PEM certificate and key
+    pub(crate) fn load<F>(certificates: &[SslCertificate], load: F) -> io::Result<Self>
+    where
+        F: Fn(&[u8], &[u8]) -> io::Result<T>,
+    {
+        let mut names = HashMap::new();
+
+        for certificate in certificates {
+            let cert = read_file(&certificate.cert, "certificate")?;
+            let key = read_file(&certificate.key, "key")?;
+            let value = load(&cert, &key)?;
+
+            for hostname in &certificate.hostnames {
+                names.insert(hostname.to_ascii_lowercase(), value.clone());
+            }
+        }
+
+        Ok(Self { names })
+    }
+}
+
+impl<T> SniMap<T> {
+    /// Value of the server name, exact names first
+    pub(crate) fn get(&self, server_name: &str) -> Option<&T> {
+        let server_name = server_name.trim_end_matches('.').to_ascii_lowercase();
+
+        self.names.get(&server_name).or_else(|| {
+            let (_, parent) = server_name.split_once('.')?;
+            self.names.get(&format!("*.{parent}"))
+        })
+    }
+}
+
+impl<T> Default for SniMap<T> {
+    fn default() -> Self {
+        Self {
+            names: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_exact_and_wildcard_names() {
+        let map = SniMap {
+            names: HashMap::from([
+                ("example.com".to_string(), 1),
+                ("*.example.com".to_string(), 2),
+            ]),
+        };
+
+        assert_eq!(map.get("Example.COM"), Some(&1));
+        assert_eq!(map.get("www.example.com"), Some(&2));
+        assert_eq!(map.get("a.b.example.com"), None);
+        assert_eq!(map.get("example.org"), None);
+    }
+}
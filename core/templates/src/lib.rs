@@ -1,4 +1,5 @@
 use handlebars::Handlebars;
+use pillow_fs::EmbeddedDir;
 use serde_json::Value;
 use tera::Tera;
 
@@ -103,6 +104,55 @@ impl Template {
             }
         }
     }
+
+    /// Return a page of views baked into the binary with `embed_dir!`
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use pillow_fs::EmbeddedDir;
+    /// use pillow_templates::Template;
+    ///
+    /// static VIEWS: EmbeddedDir = pillow_fs::embed_dir!("resources/views");
+    ///
+    /// assert_eq!(Template::Html("index_html").render_from(&VIEWS), "");
+    /// ```
+    pub fn render_from(&self, views: &EmbeddedDir) -> String {
+        let read = |path: &str| match views.get(path) {
+            Some(file) => String::from_utf8_lossy(file.content()).to_string(),
+            None => panic!("No such view ' {}/{} '", views.path(), path),
+        };
+
+        match self {
+            Template::Html(file) => read(&format!("{}.html", file)),
+
+            Template::Handlebars(hbs, value) => {
+                let mut reg = Handlebars::new();
+
+                reg.register_template_string(hbs, read(&format!("{}.hbs", hbs)))
+                    .unwrap();
+
+                reg.render(hbs, value).unwrap()
+            }
+
+            Template::Tera(name_file, ext, ctx) => {
+                let mut tera = Tera::default();
+
+                let templates = views.files().iter().filter_map(|file| {
+                    Some((file.path(), std::str::from_utf8(file.content()).ok()?))
+                });
+
+                if let Err(e) = tera.add_raw_templates(templates) {
+                    panic!("{e}")
+                }
+
+                let template_file = format!("{}.{}", name_file, ext);
+
+                tera.render(&template_file, ctx)
+                    .expect("Failed to render template")
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -136,4 +186,19 @@ mod tests {
 
         assert_eq!(page.render(), "");
     }
+
+    #[test]
+    fn it_renders_the_embedded_views() {
+        static VIEWS: EmbeddedDir = pillow_fs::embed_dir!("resources/views");
+
+        assert_eq!(Template::Html("index_html").render_from(&VIEWS), "");
+        assert_eq!(
+            Template::Handlebars("index_hbs", json!({})).render_from(&VIEWS),
+            ""
+        );
+        assert_eq!(
+            Template::Tera("index_tera", "html", Context::new()).render_from(&VIEWS),
+            ""
+        );
+    }
 }